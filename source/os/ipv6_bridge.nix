@@ -1,4 +1,4 @@
-{ override_mtu ? null }: { ... }:
+{ override_mtu ? null, pref64 ? null }: { ... }:
 let
   const = import ./constants.nix;
   mangle_ip_configure_queue = builtins.toString 0;
@@ -108,6 +108,7 @@ in
                 --nf-mark ${mangle_ip_configure_mark} \
                 --interface br0 \
                 ${lib.concatStringsSep " " (lib.lists.optionals (override_mtu != null) ["--mtu" (builtins.toString override_mtu)])} \
+                ${lib.concatStringsSep " " (lib.lists.optionals (pref64 != null) ["--pref64" pref64])} \
                 ;
             '';
         };
@@ -12,7 +12,11 @@ in
 buildSystem ({ ... }: {
   imports = [
     (import ./base.nix { ssh_authorized_keys_dir = ssh_authorized_keys_dir; ssh_authorized_key = ssh_authorized_key; })
-    (import ./ipv6_bridge.nix { override_mtu = override_mtu; })
+    (import ./ipv6_bridge.nix {
+      override_mtu = override_mtu;
+      # Jool default pool
+      pref64 = "64:ff9b::/96";
+    })
     ({ pkgs, lib, ... }: {
       config = {
         systemd.network.networks.eth0 = {
@@ -0,0 +1,268 @@
+use {
+    aargvark::Aargvark,
+    glue::unstable_ip::UnstableIpv6,
+    loga::{
+        ea,
+        Log,
+        ResultContext,
+    },
+    netlink::{
+        AddressSocket,
+        NetlinkMessage,
+        LIFETIME_INFINITE,
+    },
+    std::{
+        ffi::CString,
+        io,
+        net::Ipv6Addr,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+pub mod netlink;
+
+#[cfg(test)]
+mod test_choose;
+#[cfg(test)]
+mod test_netlink;
+
+// Address flags, from linux/if_addr.h
+pub const IFA_F_TEMPORARY: u32 = 0x01;
+pub const IFA_F_DADFAILED: u32 = 0x08;
+pub const IFA_F_DEPRECATED: u32 = 0x20;
+pub const IFA_F_TENTATIVE: u32 = 0x40;
+
+#[derive(Clone, Copy, Debug)]
+pub struct AddressCandidate {
+    pub addr: Ipv6Addr,
+    pub flags: u32,
+}
+
+impl AddressCandidate {
+    fn usable(&self) -> bool {
+        return self.flags & (IFA_F_TENTATIVE | IFA_F_DADFAILED) == 0;
+    }
+
+    fn deprecated(&self) -> bool {
+        return self.flags & IFA_F_DEPRECATED != 0;
+    }
+
+    fn temporary(&self) -> bool {
+        return self.flags & IFA_F_TEMPORARY != 0;
+    }
+}
+
+#[derive(Aargvark, Clone, Copy, Debug)]
+pub enum LinkLocalPolicy {
+    /// Don't advertise link-local addresses
+    Exclude,
+    /// Advertise a link-local address after global addresses and ULAs
+    Include,
+}
+
+struct TrackedAddress {
+    addr: Ipv6Addr,
+    flags: u32,
+    preferred_until: Option<Instant>,
+    valid_until: Option<Instant>,
+}
+
+/// The addresses of one interface, maintained from netlink messages.
+#[derive(Default)]
+pub struct AddressTracker {
+    addresses: Vec<TrackedAddress>,
+    /// Between requesting a dump and its `Done`, when `addresses` may be partial
+    dumping: bool,
+}
+
+impl AddressTracker {
+    /// Forget all addresses, before requesting a dump.
+    pub fn start_dump(&mut self) {
+        self.addresses.clear();
+        self.dumping = true;
+    }
+
+    /// Whether a dump is in progress, so the addresses may be a partial set.
+    pub fn dumping(&self) -> bool {
+        return self.dumping;
+    }
+
+    /// Apply a message received at `now`. Messages for other interfaces are ignored.
+    pub fn apply(&mut self, interface_index: u32, message: &NetlinkMessage, now: Instant) {
+        match message {
+            NetlinkMessage::NewAddress(m) if m.interface_index == interface_index => {
+                let until = |lifetime: u32| match lifetime {
+                    LIFETIME_INFINITE => None,
+                    lifetime => Some(now + Duration::from_secs(lifetime as u64)),
+                };
+                let tracked = TrackedAddress {
+                    addr: m.addr,
+                    flags: m.flags,
+                    preferred_until: m.lifetimes.and_then(|(preferred, _)| until(preferred)),
+                    valid_until: m.lifetimes.and_then(|(_, valid)| until(valid)),
+                };
+                match self.addresses.iter_mut().find(|a| a.addr == m.addr) {
+                    Some(existing) => *existing = tracked,
+                    None => self.addresses.push(tracked),
+                }
+            },
+            NetlinkMessage::DeleteAddress(m) if m.interface_index == interface_index => {
+                self.addresses.retain(|a| a.addr != m.addr);
+            },
+            NetlinkMessage::Done => {
+                self.dumping = false;
+            },
+            _ => { },
+        }
+    }
+
+    /// Current addresses, with addresses past their preferred lifetime marked
+    /// deprecated and addresses past their valid lifetime removed (in case the kernel
+    /// notification hasn't arrived yet).
+    pub fn candidates(&mut self, now: Instant) -> Vec<AddressCandidate> {
+        self.addresses.retain(|a| a.valid_until.map(|t| t > now).unwrap_or(true));
+        return self.addresses.iter().map(|a| AddressCandidate {
+            addr: a.addr,
+            flags: if a.preferred_until.map(|t| t <= now).unwrap_or(false) {
+                a.flags | IFA_F_DEPRECATED
+            } else {
+                a.flags
+            },
+        }).collect();
+    }
+
+    /// The next time an address's preferred or valid lifetime ends.
+    pub fn next_deadline(&self, now: Instant) -> Option<Instant> {
+        return self
+            .addresses
+            .iter()
+            .flat_map(|a| [a.preferred_until, a.valid_until])
+            .flatten()
+            .filter(|t| *t > now)
+            .min();
+    }
+}
+
+fn interface_index(interface: &CString) -> u32 {
+    return unsafe {
+        libc::if_nametoindex(interface.as_ptr())
+    };
+}
+
+/// Track the interface's addresses via netlink, calling `on_change` with the
+/// chosen resolver addresses initially and whenever they change. Only returns on
+/// error.
+pub fn watch_resolver_addresses(
+    log: &Log,
+    interface: &str,
+    link_local: LinkLocalPolicy,
+    mut on_change: impl FnMut(Vec<Ipv6Addr>),
+) -> Result<(), loga::Error> {
+    let interface_c =
+        CString::new(interface).context_with("Invalid interface name", ea!(interface = interface))?;
+    let mut socket = AddressSocket::open()?;
+    let mut tracker = AddressTracker::default();
+    let mut index = 0;
+    let mut resync = true;
+    let mut last_found = None;
+    loop {
+        if resync {
+            tracker.start_dump();
+            index = interface_index(&interface_c);
+            socket.request_dump()?;
+            resync = false;
+        }
+        let now = Instant::now();
+        let mut timeout = tracker.next_deadline(now).map(|t| t - now);
+        if index == 0 {
+            // Interface doesn't exist (yet), check again periodically
+            timeout = Some(timeout.unwrap_or(Duration::MAX).min(Duration::from_secs(5)));
+        }
+        let messages = match socket.recv(timeout) {
+            Ok(messages) => messages,
+            Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => {
+                log.log(loga::WARN, "Missed netlink address notifications, resynchronizing");
+                resync = true;
+                continue;
+            },
+            Err(e) => {
+                return Err(e).context("Error receiving netlink address messages");
+            },
+        };
+        let now = Instant::now();
+        for message in &messages {
+            match message {
+                NetlinkMessage::Error(e) if *e < 0 => {
+                    return Err(io::Error::from_raw_os_error(-*e)).context("Netlink address dump failed");
+                },
+                // The interface may have been recreated with a new index
+                NetlinkMessage::NewAddress(m) if
+                    m.interface_index != index && interface_index(&interface_c) == m.interface_index => {
+                    resync = true;
+                },
+                _ => { },
+            }
+            tracker.apply(index, message, now);
+        }
+        // A dump can span several datagrams, wait for all of it so a partial set isn't
+        // reported as lost addresses
+        if resync || tracker.dumping() {
+            continue;
+        }
+        let found = choose_resolver_addresses(&tracker.candidates(now), link_local);
+        if last_found.as_ref() != Some(&found) {
+            if found.is_empty() {
+                log.log_with(
+                    loga::WARN,
+                    "Interface not found or no usable ipv6 address found on interface",
+                    ea!(interface = interface),
+                );
+            }
+            on_change(found.clone());
+            last_found = Some(found);
+        }
+        if index == 0 {
+            resync = true;
+        }
+    }
+}
+
+/// Choose the addresses to advertise as resolvers, in order of preference:
+///
+/// * Global addresses - stable (non-temporary) non-deprecated addresses if any, or
+///   else temporary non-deprecated addresses, or else deprecated addresses
+///
+/// * All ULAs, since they don't change when the global prefix does
+///
+/// * One link-local address, if requested
+///
+/// Addresses that are tentative or failed DAD are never chosen.
+pub fn choose_resolver_addresses(candidates: &[AddressCandidate], link_local: LinkLocalPolicy) -> Vec<Ipv6Addr> {
+    let mut out = vec![];
+    let globals =
+        candidates.iter().filter(|c| c.usable() && c.addr.unstable_is_global()).collect::<Vec<_>>();
+    let stable_globals = globals.iter().filter(|c| !c.deprecated() && !c.temporary()).collect::<Vec<_>>();
+    let current_globals = globals.iter().filter(|c| !c.deprecated()).collect::<Vec<_>>();
+    if !stable_globals.is_empty() {
+        out.extend(stable_globals.iter().map(|c| c.addr));
+    } else if !current_globals.is_empty() {
+        out.extend(current_globals.iter().map(|c| c.addr));
+    } else {
+        out.extend(globals.iter().map(|c| c.addr));
+    }
+    out.extend(candidates.iter().filter(|c| c.usable() && c.addr.unstable_is_unique_local()).map(|c| c.addr));
+    if let LinkLocalPolicy::Include = link_local {
+        out.extend(
+            candidates
+                .iter()
+                .filter(|c| c.usable() && c.addr.unstable_is_unicast_link_local())
+                .map(|c| c.addr)
+                .take(1),
+        );
+    }
+    out.dedup();
+    return out;
+}
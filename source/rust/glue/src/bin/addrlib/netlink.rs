@@ -0,0 +1,242 @@
+use {
+    loga::ResultContext,
+    std::{
+        io,
+        mem,
+        net::Ipv6Addr,
+        os::fd::{
+            AsRawFd,
+            FromRawFd,
+            OwnedFd,
+        },
+        time::Duration,
+    },
+};
+
+// Netlink, rtnetlink address messages. Everything is in host byte order.
+//
+// * https://man7.org/linux/man-pages/man7/netlink.7.html
+//
+// * https://man7.org/linux/man-pages/man7/rtnetlink.7.html
+const NLMSG_HEADER_SIZE: usize = 16;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+const NLM_F_REQUEST: u16 = 0x01;
+const NLM_F_DUMP: u16 = 0x300;
+const RTM_NEWADDR: u16 = 20;
+const RTM_DELADDR: u16 = 21;
+const RTM_GETADDR: u16 = 22;
+const IFADDRMSG_SIZE: usize = 8;
+const RTATTR_HEADER_SIZE: usize = 4;
+const IFA_ADDRESS: u16 = 1;
+const IFA_LOCAL: u16 = 2;
+const IFA_CACHEINFO: u16 = 6;
+const IFA_FLAGS: u16 = 8;
+const RTMGRP_IPV6_IFADDR: u32 = 0x100;
+
+/// Lifetime value meaning the address never expires
+pub const LIFETIME_INFINITE: u32 = 0xffffffff;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AddressMessage {
+    pub interface_index: u32,
+    pub addr: Ipv6Addr,
+    /// `IFA_F_` flags
+    pub flags: u32,
+    /// Remaining preferred and valid lifetimes (seconds), if reported
+    pub lifetimes: Option<(u32, u32)>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum NetlinkMessage {
+    NewAddress(AddressMessage),
+    DeleteAddress(AddressMessage),
+    /// End of a dump
+    Done,
+    /// Negative errno, or 0 for an ack
+    Error(i32),
+    Other,
+}
+
+fn align(length: usize) -> usize {
+    return (length + 3) & !3;
+}
+
+fn read_ne_u16(source: &[u8], start: usize) -> Option<u16> {
+    return Some(u16::from_ne_bytes(source.get(start .. start + 2)?.try_into().unwrap()));
+}
+
+fn read_ne_u32(source: &[u8], start: usize) -> Option<u32> {
+    return Some(u32::from_ne_bytes(source.get(start .. start + 4)?.try_into().unwrap()));
+}
+
+/// Parse the messages in a single netlink datagram. Non-IPv6 address messages are
+/// returned as `Other`.
+pub fn parse_messages(source: &[u8]) -> Option<Vec<NetlinkMessage>> {
+    let mut out = vec![];
+    let mut at_message_start = 0;
+    while at_message_start + NLMSG_HEADER_SIZE <= source.len() {
+        let message_length = read_ne_u32(source, at_message_start)? as usize;
+        if message_length < NLMSG_HEADER_SIZE {
+            return None;
+        }
+        let message_type = read_ne_u16(source, at_message_start + 4)?;
+        let body = source.get(at_message_start + NLMSG_HEADER_SIZE .. at_message_start + message_length)?;
+        out.push(match message_type {
+            NLMSG_DONE => NetlinkMessage::Done,
+            NLMSG_ERROR => NetlinkMessage::Error(read_ne_u32(body, 0)? as i32),
+            RTM_NEWADDR | RTM_DELADDR => match parse_address(body)? {
+                Some(address) if message_type == RTM_NEWADDR => NetlinkMessage::NewAddress(address),
+                Some(address) => NetlinkMessage::DeleteAddress(address),
+                None => NetlinkMessage::Other,
+            },
+            _ => NetlinkMessage::Other,
+        });
+        at_message_start += align(message_length);
+    }
+    return Some(out);
+}
+
+/// Source is the body after the netlink header. Returns `Some(None)` for non-IPv6
+/// addresses.
+fn parse_address(source: &[u8]) -> Option<Option<AddressMessage>> {
+    // ifaddrmsg: family, prefix length, flags, scope, interface index
+    if *source.first()? != libc::AF_INET6 as u8 {
+        return Some(None);
+    }
+    let mut flags = *source.get(2)? as u32;
+    let interface_index = read_ne_u32(source, 4)?;
+    let mut address = None;
+    let mut local = None;
+    let mut lifetimes = None;
+    let mut at_attr_start = IFADDRMSG_SIZE;
+    while at_attr_start + RTATTR_HEADER_SIZE <= source.len() {
+        let attr_length = read_ne_u16(source, at_attr_start)? as usize;
+        if attr_length < RTATTR_HEADER_SIZE {
+            return None;
+        }
+        let attr_type = read_ne_u16(source, at_attr_start + 2)?;
+        let data = source.get(at_attr_start + RTATTR_HEADER_SIZE .. at_attr_start + attr_length)?;
+        match attr_type {
+            IFA_ADDRESS => address = Some(Ipv6Addr::from(<[u8; 16]>::try_from(data).ok()?)),
+            IFA_LOCAL => local = Some(Ipv6Addr::from(<[u8; 16]>::try_from(data).ok()?)),
+            // ifa_cacheinfo: preferred, valid, created timestamp, updated timestamp
+            IFA_CACHEINFO => lifetimes = Some((read_ne_u32(data, 0)?, read_ne_u32(data, 4)?)),
+            // Full flags, the header only has the low 8 bits
+            IFA_FLAGS => flags = read_ne_u32(data, 0)?,
+            _ => { },
+        }
+        at_attr_start += align(attr_length);
+    }
+
+    // For point to point links address is the peer and local is this side
+    let addr = local.or(address)?;
+    return Some(Some(AddressMessage {
+        interface_index: interface_index,
+        addr: addr,
+        flags: flags,
+        lifetimes: lifetimes,
+    }));
+}
+
+/// A netlink socket subscribed to IPv6 address changes.
+pub struct AddressSocket {
+    fd: OwnedFd,
+    seq: u32,
+    buffer: Vec<u8>,
+}
+
+impl AddressSocket {
+    pub fn open() -> Result<Self, loga::Error> {
+        let fd = unsafe {
+            libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, libc::NETLINK_ROUTE)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error()).context("Error opening netlink socket");
+        }
+        let fd = unsafe {
+            OwnedFd::from_raw_fd(fd)
+        };
+        let mut addr: libc::sockaddr_nl = unsafe {
+            mem::zeroed()
+        };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = RTMGRP_IPV6_IFADDR;
+        let res = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error()).context("Error subscribing to netlink address changes");
+        }
+        return Ok(AddressSocket {
+            fd: fd,
+            seq: 0,
+            buffer: vec![0; 65536],
+        });
+    }
+
+    /// Request all current IPv6 addresses, which will be received as `NewAddress`
+    /// messages followed by `Done`.
+    pub fn request_dump(&mut self) -> Result<(), loga::Error> {
+        self.seq = self.seq.wrapping_add(1);
+        let mut message = vec![];
+        message.extend_from_slice(&((NLMSG_HEADER_SIZE + IFADDRMSG_SIZE) as u32).to_ne_bytes());
+        message.extend_from_slice(&RTM_GETADDR.to_ne_bytes());
+        message.extend_from_slice(&(NLM_F_REQUEST | NLM_F_DUMP).to_ne_bytes());
+        message.extend_from_slice(&self.seq.to_ne_bytes());
+
+        // Port id, 0 is the kernel
+        message.extend_from_slice(&0u32.to_ne_bytes());
+        message.extend_from_slice(&[libc::AF_INET6 as u8, 0, 0, 0]);
+        message.extend_from_slice(&0u32.to_ne_bytes());
+        let res = unsafe {
+            libc::send(self.fd.as_raw_fd(), message.as_ptr() as *const libc::c_void, message.len(), 0)
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error()).context("Error requesting netlink address dump");
+        }
+        return Ok(());
+    }
+
+    /// Wait up to `timeout` for messages. Returns an empty list on timeout. Returns
+    /// `Err` with `ENOBUFS` if notifications were dropped, after which a new dump is
+    /// needed to resynchronize.
+    pub fn recv(&mut self, timeout: Option<Duration>) -> Result<Vec<NetlinkMessage>, io::Error> {
+        let mut pollfd = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = match timeout {
+            Some(timeout) => timeout.as_millis().min(i32::MAX as u128) as i32,
+            None => -1,
+        };
+        let res = unsafe {
+            libc::poll(&mut pollfd, 1, timeout)
+        };
+        if res < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                return Ok(vec![]);
+            }
+            return Err(e);
+        }
+        if res == 0 {
+            return Ok(vec![]);
+        }
+        let res = unsafe {
+            libc::recv(self.fd.as_raw_fd(), self.buffer.as_mut_ptr() as *mut libc::c_void, self.buffer.len(), 0)
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        return match parse_messages(&self.buffer[.. res as usize]) {
+            Some(messages) => Ok(messages),
+            None => Err(io::Error::new(io::ErrorKind::InvalidData, "Malformed netlink message")),
+        };
+    }
+}
@@ -0,0 +1,49 @@
+use {
+    crate::addrlib::{
+        choose_resolver_addresses,
+        AddressCandidate,
+        LinkLocalPolicy,
+        IFA_F_DEPRECATED,
+        IFA_F_TEMPORARY,
+        IFA_F_TENTATIVE,
+    },
+    std::net::Ipv6Addr,
+};
+
+fn candidate(addr: &str, flags: u32) -> AddressCandidate {
+    return AddressCandidate {
+        addr: addr.parse().unwrap(),
+        flags: flags,
+    };
+}
+
+fn addrs(addrs: &[&str]) -> Vec<Ipv6Addr> {
+    return addrs.iter().map(|a| a.parse().unwrap()).collect();
+}
+
+#[test]
+fn test_prefer_stable() {
+    let candidates = [
+        candidate("2400::1", IFA_F_DEPRECATED),
+        candidate("2400::2", IFA_F_TEMPORARY),
+        candidate("2400::3", 0),
+        candidate("2400::4", IFA_F_TENTATIVE),
+        candidate("fd00::1", 0),
+        candidate("fe80::1", 0),
+    ];
+    assert_eq!(choose_resolver_addresses(&candidates, LinkLocalPolicy::Exclude), addrs(&["2400::3", "fd00::1"]));
+    assert_eq!(
+        choose_resolver_addresses(&candidates, LinkLocalPolicy::Include),
+        addrs(&["2400::3", "fd00::1", "fe80::1"])
+    );
+}
+
+#[test]
+fn test_fallback() {
+    let candidates = [candidate("2400::1", IFA_F_DEPRECATED), candidate("2400::2", IFA_F_TEMPORARY)];
+    assert_eq!(choose_resolver_addresses(&candidates, LinkLocalPolicy::Exclude), addrs(&["2400::2"]));
+    let candidates = [candidate("2400::1", IFA_F_DEPRECATED), candidate("fd00::1", IFA_F_DEPRECATED)];
+    assert_eq!(choose_resolver_addresses(&candidates, LinkLocalPolicy::Exclude), addrs(&["2400::1", "fd00::1"]));
+    let candidates = [candidate("2400::1", IFA_F_TENTATIVE)];
+    assert_eq!(choose_resolver_addresses(&candidates, LinkLocalPolicy::Exclude), addrs(&[]));
+}
@@ -0,0 +1,117 @@
+use {
+    crate::addrlib::{
+        netlink::{
+            parse_messages,
+            AddressMessage,
+            NetlinkMessage,
+        },
+        AddressCandidate,
+        AddressTracker,
+        IFA_F_DEPRECATED,
+        IFA_F_TENTATIVE,
+    },
+    std::{
+        net::Ipv6Addr,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+fn new_address_message(index: u32, addr: Ipv6Addr, flags: u32, lifetimes: (u32, u32)) -> Vec<u8> {
+    let mut out = vec![];
+
+    // nlmsghdr: length, RTM_NEWADDR, flags, seq, pid
+    out.extend_from_slice(&56u32.to_ne_bytes());
+    out.extend_from_slice(&20u16.to_ne_bytes());
+    out.extend_from_slice(&0u16.to_ne_bytes());
+    out.extend_from_slice(&0u32.to_ne_bytes());
+    out.extend_from_slice(&0u32.to_ne_bytes());
+
+    // ifaddrmsg: AF_INET6, prefix length, low flags, scope, index
+    out.extend_from_slice(&[10, 64, flags as u8, 0]);
+    out.extend_from_slice(&index.to_ne_bytes());
+
+    // IFA_ADDRESS
+    out.extend_from_slice(&20u16.to_ne_bytes());
+    out.extend_from_slice(&1u16.to_ne_bytes());
+    out.extend_from_slice(&addr.octets());
+
+    // IFA_CACHEINFO, only the lifetimes
+    out.extend_from_slice(&12u16.to_ne_bytes());
+    out.extend_from_slice(&6u16.to_ne_bytes());
+    out.extend_from_slice(&lifetimes.0.to_ne_bytes());
+    out.extend_from_slice(&lifetimes.1.to_ne_bytes());
+    return out;
+}
+
+#[test]
+fn test_parse_new_address() {
+    let addr = "2400::1".parse().unwrap();
+    let mut source = new_address_message(3, addr, IFA_F_TENTATIVE, (100, 200));
+
+    // Second message, NLMSG_DONE
+    source.extend_from_slice(&20u32.to_ne_bytes());
+    source.extend_from_slice(&3u16.to_ne_bytes());
+    source.extend_from_slice(&[0; 14]);
+    assert_eq!(parse_messages(&source).unwrap(), vec![NetlinkMessage::NewAddress(AddressMessage {
+        interface_index: 3,
+        addr: addr,
+        flags: IFA_F_TENTATIVE,
+        lifetimes: Some((100, 200)),
+    }), NetlinkMessage::Done]);
+}
+
+#[test]
+fn test_parse_truncated() {
+    let source = new_address_message(3, "2400::1".parse().unwrap(), 0, (100, 200));
+    assert!(parse_messages(&source[.. 40]).is_none());
+}
+
+#[test]
+fn test_tracker_lifetimes() {
+    let now = Instant::now();
+    let addr = "2400::1".parse().unwrap();
+    let message = |flags: u32| NetlinkMessage::NewAddress(AddressMessage {
+        interface_index: 3,
+        addr: addr,
+        flags: flags,
+        lifetimes: Some((100, 200)),
+    });
+    let mut tracker = AddressTracker::default();
+    tracker.apply(4, &message(0), now);
+    assert_eq!(tracker.candidates(now).len(), 0);
+    tracker.apply(3, &message(IFA_F_TENTATIVE), now);
+    tracker.apply(3, &message(0), now);
+    let flags = |candidates: Vec<AddressCandidate>| candidates.iter().map(|c| c.flags).collect::<Vec<_>>();
+    assert_eq!(flags(tracker.candidates(now)), vec![0]);
+    assert_eq!(tracker.next_deadline(now), Some(now + Duration::from_secs(100)));
+    let later = now + Duration::from_secs(150);
+    assert_eq!(flags(tracker.candidates(later)), vec![IFA_F_DEPRECATED]);
+    assert_eq!(tracker.next_deadline(later), Some(now + Duration::from_secs(200)));
+    assert_eq!(flags(tracker.candidates(now + Duration::from_secs(200))), Vec::<u32>::new());
+}
+
+#[test]
+fn test_tracker_dump() {
+    let now = Instant::now();
+    let message = |addr: &str| NetlinkMessage::NewAddress(AddressMessage {
+        interface_index: 3,
+        addr: addr.parse().unwrap(),
+        flags: 0,
+        lifetimes: None,
+    });
+    let mut tracker = AddressTracker::default();
+    assert!(!tracker.dumping());
+    tracker.apply(3, &message("2400::1"), now);
+    tracker.start_dump();
+    assert!(tracker.dumping());
+    assert_eq!(tracker.candidates(now).len(), 0);
+    tracker.apply(3, &message("2400::1"), now);
+    assert!(tracker.dumping());
+    tracker.apply(3, &message("fd00::1"), now);
+    tracker.apply(3, &NetlinkMessage::Done, now);
+    assert!(!tracker.dumping());
+    assert_eq!(tracker.candidates(now).len(), 2);
+}
@@ -0,0 +1,346 @@
+use {
+    loga::{
+        ea,
+        ResultContext,
+    },
+    std::{
+        ffi::CString,
+        io,
+        mem,
+        net::Ipv6Addr,
+        path::Path,
+        os::fd::{
+            AsRawFd,
+            FromRawFd,
+            OwnedFd,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+pub mod rs;
+
+#[cfg(test)]
+mod test_announce;
+#[cfg(test)]
+mod test_rs;
+
+/// Routers beyond this many replace the least recently seen, so spoofed sources
+/// can't grow the cache without bound.
+const CACHED_ROUTERS_MAX: usize = 8;
+/// Cached RAs older than this aren't sent, in case the router is gone, even if the
+/// router lifetime is longer. The default router lifetime (3 × MaxRtrAdvInterval).
+///
+/// * https://datatracker.ietf.org/doc/html/rfc4861#section-6.2.1
+const CACHED_RA_MAX_AGE: Duration = Duration::from_secs(1800);
+// * https://datatracker.ietf.org/doc/html/rfc4861#section-10
+pub const MAX_RA_DELAY_TIME: Duration = Duration::from_millis(500);
+const MIN_DELAY_BETWEEN_RAS: Duration = Duration::from_secs(3);
+// * https://man7.org/linux/man-pages/man7/packet.7.html
+pub const ETH_P_IPV6: u16 = 0x86dd;
+
+/// The most recent upstream RA from a router, before rewriting.
+pub struct CachedRa {
+    pub source: Ipv6Addr,
+    pub packet: Vec<u8>,
+    pub received: Instant,
+    /// After the router lifetime, capped at `CACHED_RA_MAX_AGE`
+    pub expires: Instant,
+}
+
+#[derive(Default)]
+pub struct RaCache {
+    ras: Vec<CachedRa>,
+}
+
+impl RaCache {
+    /// `packet` should be a validated RA from `source`, with the router lifetime
+    /// (seconds) from its header. A zero lifetime replaces any earlier RA from the
+    /// router and is never sent.
+    pub fn record(&mut self, source: Ipv6Addr, packet: &[u8], router_lifetime: u16, now: Instant) {
+        let entry = CachedRa {
+            source: source,
+            packet: packet.to_vec(),
+            received: now,
+            expires: now + Duration::from_secs(router_lifetime as u64).min(CACHED_RA_MAX_AGE),
+        };
+        if let Some(cached) = self.ras.iter_mut().find(|c| c.source == source) {
+            *cached = entry;
+            return;
+        }
+        if self.ras.len() >= CACHED_ROUTERS_MAX {
+            let oldest = self.ras.iter_mut().min_by_key(|c| c.received).unwrap();
+            *oldest = entry;
+            return;
+        }
+        self.ras.push(entry);
+    }
+
+    /// RAs whose router lifetime hasn't run out, to send.
+    pub fn fresh(&self, now: Instant) -> impl Iterator<Item = &CachedRa> {
+        return self.ras.iter().filter(move |c| now < c.expires);
+    }
+}
+
+/// When to send multicast RAs in response to router solicitations. Solicitations
+/// while a response is pending share the response.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc4861#section-6.2.6
+#[derive(Default)]
+pub struct RaSchedule {
+    last_sent: Option<Instant>,
+    next: Option<Instant>,
+}
+
+impl RaSchedule {
+    /// `delay` should be random, up to `MAX_RA_DELAY_TIME`.
+    pub fn solicited(&mut self, now: Instant, delay: Duration) {
+        if self.next.is_some() {
+            return;
+        }
+        let mut next = now + delay;
+        if let Some(last_sent) = self.last_sent {
+            next = next.max(last_sent + MIN_DELAY_BETWEEN_RAS);
+        }
+        self.next = Some(next);
+    }
+
+    pub fn next(&self) -> Option<Instant> {
+        return self.next;
+    }
+
+    /// Returns whether a response is due, and if so records it as sent.
+    pub fn take_due(&mut self, now: Instant) -> bool {
+        if self.next.is_none_or(|next| next > now) {
+            return false;
+        }
+        self.next = None;
+        self.last_sent = Some(now);
+        return true;
+    }
+
+    /// Record a multicast RA sent for another reason.
+    pub fn sent(&mut self, now: Instant) {
+        self.last_sent = Some(now);
+    }
+}
+
+/// The ethernet address for an IPv6 multicast destination.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc2464#section-7
+pub fn multicast_mac(destination: Ipv6Addr) -> [u8; 6] {
+    let octets = destination.octets();
+    return [0x33, 0x33, octets[12], octets[13], octets[14], octets[15]];
+}
+
+/// Sends IPv6 packets on an interface, with the link layer header filled in by
+/// the kernel. Packets sent on a bridge go out every port, so this should be a
+/// bridge port.
+pub struct PacketSocket {
+    fd: OwnedFd,
+    ifindex: i32,
+    interface: String,
+}
+
+/// The interface index of a bridge port. RAs sent on the bridge would also reach
+/// the upstream router, from its own source address, and solicitations or DHCPv6
+/// requests received on it could come from the upstream side, so bridges are
+/// refused.
+pub fn port_ifindex(interface: &str) -> Result<i32, loga::Error> {
+    let name = CString::new(interface).context_with("Invalid interface name", ea!(interface = interface))?;
+    let ifindex = unsafe {
+        libc::if_nametoindex(name.as_ptr())
+    };
+    if ifindex == 0 {
+        return Err(
+            io::Error::last_os_error(),
+        ).context_with("Error looking up interface", ea!(interface = interface));
+    }
+    if Path::new("/sys/class/net").join(interface).join("bridge").exists() {
+        return Err(
+            loga::err_with(
+                "Interface is a bridge, use its LAN-facing ports instead",
+                ea!(interface = interface),
+            ),
+        );
+    }
+    return Ok(ifindex as i32);
+}
+
+impl PacketSocket {
+    pub fn open(interface: &str) -> Result<Self, loga::Error> {
+        let ifindex = port_ifindex(interface)?;
+
+        // Protocol 0, only for sending
+        let fd = unsafe {
+            libc::socket(libc::AF_PACKET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error()).context("Error opening packet socket");
+        }
+        let fd = unsafe {
+            OwnedFd::from_raw_fd(fd)
+        };
+        return Ok(PacketSocket {
+            fd: fd,
+            ifindex: ifindex,
+            interface: interface.to_string(),
+        });
+    }
+
+    pub fn interface(&self) -> &str {
+        return &self.interface;
+    }
+
+    /// Send a packet with a multicast destination.
+    pub fn send_multicast(&self, packet: &[u8], destination: Ipv6Addr) -> Result<(), io::Error> {
+        let mut addr: libc::sockaddr_ll = unsafe {
+            mem::zeroed()
+        };
+        addr.sll_family = libc::AF_PACKET as libc::c_ushort;
+        addr.sll_protocol = ETH_P_IPV6.to_be();
+        addr.sll_ifindex = self.ifindex;
+        addr.sll_halen = 6;
+        addr.sll_addr[.. 6].copy_from_slice(&multicast_mac(destination));
+        let res = unsafe {
+            libc::sendto(
+                self.fd.as_raw_fd(),
+                packet.as_ptr() as *const libc::c_void,
+                packet.len(),
+                0,
+                &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        return Ok(());
+    }
+}
+
+pub fn bpf(code: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    return libc::sock_filter {
+        code: code as u16,
+        jt: jt,
+        jf: jf,
+        k: k,
+    };
+}
+
+/// Receives IPv6 packets directly from bridge ports, so the port each arrived on is
+/// known and packets from other interfaces, like the upstream side of the bridge,
+/// can be ignored.
+pub struct PortReceiver {
+    fd: OwnedFd,
+    ifindexes: Vec<i32>,
+}
+
+impl PortReceiver {
+    /// `filter` is a classic BPF program run on each packet (from the IPv6 header)
+    /// to keep only the wanted ones, so the socket doesn't copy all traffic.
+    pub fn open(ports: &[&str], filter: &mut [libc::sock_filter]) -> Result<Self, loga::Error> {
+        let mut ifindexes = vec![];
+        for port in ports {
+            ifindexes.push(port_ifindex(port)?);
+        }
+
+        // Frames arriving on bridge ports are only delivered to sockets for all
+        // protocols, before the bridge takes them
+        let protocol = (libc::ETH_P_ALL as u16).to_be() as libc::c_int;
+        let fd = unsafe {
+            libc::socket(libc::AF_PACKET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, protocol)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error()).context("Error opening packet socket");
+        }
+        let fd = unsafe {
+            OwnedFd::from_raw_fd(fd)
+        };
+        let program = libc::sock_fprog {
+            len: filter.len() as u16,
+            filter: filter.as_mut_ptr(),
+        };
+        let res = unsafe {
+            libc::setsockopt(
+                fd.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_ATTACH_FILTER,
+                &program as *const libc::sock_fprog as *const libc::c_void,
+                mem::size_of::<libc::sock_fprog>() as libc::socklen_t,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error()).context("Error attaching packet filter");
+        }
+        return Ok(PortReceiver {
+            fd: fd,
+            ifindexes: ifindexes,
+        });
+    }
+
+    /// Wait up to `timeout` (forever if `None`) for a packet of at most
+    /// `max_size` bytes. Returns the packet and the index of the port it arrived on,
+    /// or `None` on timeout, or if the packet was too large, sent by this host, or
+    /// received on another interface.
+    pub fn recv(&self, timeout: Option<Duration>, max_size: usize) -> Result<Option<(i32, Vec<u8>)>, io::Error> {
+        let timeout_ms = match timeout {
+            // Round up, so the caller doesn't wake early and spin
+            Some(timeout) => (timeout + Duration::from_nanos(999_999)).as_millis().min(i32::MAX as u128) as i32,
+            None => -1,
+        };
+        let mut poll_fd = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let res = unsafe {
+            libc::poll(&mut poll_fd, 1, timeout_ms)
+        };
+        if res < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                return Ok(None);
+            }
+            return Err(e);
+        }
+        if res == 0 {
+            return Ok(None);
+        }
+        let mut buffer = vec![0u8; max_size];
+        let mut addr: libc::sockaddr_ll = unsafe {
+            mem::zeroed()
+        };
+        let mut addr_len = mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+
+        // With MSG_TRUNC the full packet size is returned
+        let size = unsafe {
+            libc::recvfrom(
+                self.fd.as_raw_fd(),
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len(),
+                libc::MSG_TRUNC,
+                &mut addr as *mut libc::sockaddr_ll as *mut libc::sockaddr,
+                &mut addr_len,
+            )
+        };
+        if size < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                return Ok(None);
+            }
+            return Err(e);
+        }
+        if size as usize > buffer.len() {
+            return Ok(None);
+        }
+        if addr.sll_pkttype == libc::PACKET_OUTGOING || !self.ifindexes.contains(&addr.sll_ifindex) {
+            return Ok(None);
+        }
+        buffer.truncate(size as usize);
+        return Ok(Some((addr.sll_ifindex, buffer)));
+    }
+}
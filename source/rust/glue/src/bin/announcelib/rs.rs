@@ -0,0 +1,108 @@
+use {
+    super::{
+        bpf,
+        PortReceiver,
+        ETH_P_IPV6,
+    },
+    crate::manglelib::checked_icmpv6,
+    std::{
+        io,
+        net::Ipv6Addr,
+        time::Duration,
+    },
+};
+
+// * https://datatracker.ietf.org/doc/html/rfc4861#section-4.1
+const ICMPV6_TYPE_RS: u8 = 133;
+const RS_FIXED_HEADER_SIZE: usize = 8;
+const OPT_SOURCE_LINK_LAYER_ADDRESS: u8 = 1;
+// * https://datatracker.ietf.org/doc/html/rfc8200#section-3
+const IPV6_FIXED_HEADER_SIZE: usize = 40;
+const NEXT_HEADER_ICMPV6: u8 = 58;
+/// Solicitations are small, larger messages are dropped.
+const RECV_BUFFER_SIZE: usize = 1500;
+
+/// Check a router solicitation received with `hop_limit` from `source`. `message`
+/// is the ICMPv6 message, with the checksum already verified.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc4861#section-6.1.1
+pub fn valid_rs(source: Ipv6Addr, hop_limit: u8, message: &[u8]) -> bool {
+    if hop_limit != 255 {
+        return false;
+    }
+    if message.len() < RS_FIXED_HEADER_SIZE || message[0] != ICMPV6_TYPE_RS || message[1] != 0 {
+        return false;
+    }
+    let mut options = &message[RS_FIXED_HEADER_SIZE..];
+    while !options.is_empty() {
+        let Some(&[type_, length]) = options.get(.. 2) else {
+            return false;
+        };
+        let size = length as usize * 8;
+        if size == 0 || size > options.len() {
+            return false;
+        }
+        if type_ == OPT_SOURCE_LINK_LAYER_ADDRESS && source.is_unspecified() {
+            return false;
+        }
+        options = &options[size..];
+    }
+    return true;
+}
+
+pub struct Solicitation {
+    pub source: Ipv6Addr,
+    pub hop_limit: u8,
+    pub message: Vec<u8>,
+}
+
+/// Split an IPv6 packet into a `Solicitation` if it's an ICMPv6 router solicitation
+/// with a correct checksum, without validating the solicitation itself.
+pub fn parse_solicitation(packet: &[u8]) -> Option<Solicitation> {
+    if packet.first()? >> 4 != 6 {
+        return None;
+    }
+    let message = checked_icmpv6(packet)?;
+    if message.first() != Some(&ICMPV6_TYPE_RS) {
+        return None;
+    }
+    return Some(Solicitation {
+        source: Ipv6Addr::from(<[u8; 16]>::try_from(&packet[8 .. 24]).unwrap()),
+        hop_limit: packet[7],
+        message: message.to_vec(),
+    });
+}
+
+/// Receives router solicitations on bridge ports. Solicitations from other
+/// interfaces, like the upstream side of the bridge, are ignored.
+pub struct RsSocket {
+    receiver: PortReceiver,
+}
+
+impl RsSocket {
+    pub fn open(ports: &[&str]) -> Result<Self, loga::Error> {
+        // Keep only IPv6 with ICMPv6 directly after the fixed header and type RS.
+        // Everything is checked again after receiving.
+        let mut filter = [
+            bpf(libc::BPF_LD | libc::BPF_H | libc::BPF_ABS, (libc::SKF_AD_OFF + libc::SKF_AD_PROTOCOL) as u32, 0, 0),
+            bpf(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, ETH_P_IPV6 as u32, 0, 5),
+            bpf(libc::BPF_LD | libc::BPF_B | libc::BPF_ABS, 6, 0, 0),
+            bpf(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, NEXT_HEADER_ICMPV6 as u32, 0, 3),
+            bpf(libc::BPF_LD | libc::BPF_B | libc::BPF_ABS, IPV6_FIXED_HEADER_SIZE as u32, 0, 0),
+            bpf(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, ICMPV6_TYPE_RS as u32, 0, 1),
+            bpf(libc::BPF_RET | libc::BPF_K, u32::MAX, 0, 0),
+            bpf(libc::BPF_RET | libc::BPF_K, 0, 0, 0),
+        ];
+        return Ok(RsSocket { receiver: PortReceiver::open(ports, &mut filter)? });
+    }
+
+    /// Wait up to `timeout` (forever if `None`) for a solicitation. Returns `None`
+    /// on timeout, or if the packet was truncated, sent by this host, received on
+    /// another interface, or not a solicitation.
+    pub fn recv(&self, timeout: Option<Duration>) -> Result<Option<Solicitation>, io::Error> {
+        let Some((_, packet)) = self.receiver.recv(timeout, RECV_BUFFER_SIZE)? else {
+            return Ok(None);
+        };
+        return Ok(parse_solicitation(&packet));
+    }
+}
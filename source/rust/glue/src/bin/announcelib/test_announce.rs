@@ -0,0 +1,108 @@
+use {
+    super::{
+        multicast_mac,
+        RaCache,
+        RaSchedule,
+        CACHED_RA_MAX_AGE,
+        CACHED_ROUTERS_MAX,
+        MIN_DELAY_BETWEEN_RAS,
+    },
+    std::{
+        net::Ipv6Addr,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+fn router(i: u16) -> Ipv6Addr {
+    return Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, i);
+}
+
+#[test]
+fn test_cache_replaces_same_router() {
+    let now = Instant::now();
+    let mut cache = RaCache::default();
+    cache.record(router(1), &[1], 1800, now);
+    cache.record(router(2), &[2], 1800, now);
+    cache.record(router(1), &[3], 1800, now + Duration::from_secs(1));
+    let got = cache.fresh(now).map(|c| (c.source, c.packet.clone())).collect::<Vec<_>>();
+    assert_eq!(got, vec![(router(1), vec![3]), (router(2), vec![2])]);
+}
+
+#[test]
+fn test_cache_bounded() {
+    let now = Instant::now();
+    let mut cache = RaCache::default();
+    for i in 0 .. CACHED_ROUTERS_MAX as u16 {
+        cache.record(router(i), &[], 1800, now + Duration::from_secs(i as u64));
+    }
+
+    // Replaces the least recently seen
+    cache.record(router(100), &[], 1800, now + Duration::from_secs(100));
+    let got = cache.fresh(now).map(|c| c.source).collect::<Vec<_>>();
+    assert_eq!(got.len(), CACHED_ROUTERS_MAX);
+    assert!(!got.contains(&router(0)));
+    assert!(got.contains(&router(100)));
+}
+
+#[test]
+fn test_cache_expires() {
+    let now = Instant::now();
+    let mut cache = RaCache::default();
+    cache.record(router(1), &[], 1800, now);
+    cache.record(router(2), &[], 1800, now + Duration::from_secs(60));
+    let got = cache.fresh(now + CACHED_RA_MAX_AGE + Duration::from_secs(1)).map(|c| c.source).collect::<Vec<_>>();
+    assert_eq!(got, vec![router(2)]);
+}
+
+#[test]
+fn test_cache_router_lifetime() {
+    let now = Instant::now();
+    let mut cache = RaCache::default();
+    cache.record(router(1), &[], 60, now);
+    cache.record(router(2), &[], 9000, now);
+    let got = cache.fresh(now + Duration::from_secs(60)).map(|c| c.source).collect::<Vec<_>>();
+    assert_eq!(got, vec![router(2)]);
+
+    // The router stopped being a default router
+    cache.record(router(2), &[], 0, now + Duration::from_secs(1));
+    let got = cache.fresh(now + Duration::from_secs(1)).map(|c| c.source).collect::<Vec<_>>();
+    assert_eq!(got, vec![router(1)]);
+}
+
+#[test]
+fn test_schedule_coalesces() {
+    let now = Instant::now();
+    let mut schedule = RaSchedule::default();
+    schedule.solicited(now, Duration::from_millis(100));
+    schedule.solicited(now + Duration::from_millis(50), Duration::from_millis(400));
+    assert_eq!(schedule.next(), Some(now + Duration::from_millis(100)));
+    assert!(!schedule.take_due(now + Duration::from_millis(99)));
+    assert!(schedule.take_due(now + Duration::from_millis(100)));
+    assert_eq!(schedule.next(), None);
+    assert!(!schedule.take_due(now + Duration::from_millis(200)));
+}
+
+#[test]
+fn test_schedule_min_delay() {
+    let now = Instant::now();
+    let mut schedule = RaSchedule::default();
+    schedule.solicited(now, Duration::ZERO);
+    assert!(schedule.take_due(now));
+    schedule.solicited(now + Duration::from_secs(1), Duration::from_millis(100));
+    assert_eq!(schedule.next(), Some(now + MIN_DELAY_BETWEEN_RAS));
+
+    // Unsolicited RAs count too
+    let mut schedule = RaSchedule::default();
+    schedule.sent(now);
+    schedule.solicited(now, Duration::ZERO);
+    assert_eq!(schedule.next(), Some(now + MIN_DELAY_BETWEEN_RAS));
+}
+
+#[test]
+fn test_multicast_mac() {
+    assert_eq!(multicast_mac(Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1)), [0x33, 0x33, 0, 0, 0, 1]);
+    assert_eq!(multicast_mac(Ipv6Addr::new(0xff02, 0, 0, 0, 0, 1, 0xff00, 0x1234)), [0x33, 0x33, 0xff, 0, 0x12, 0x34]);
+}
@@ -0,0 +1,103 @@
+use {
+    super::rs::{
+        parse_solicitation,
+        valid_rs,
+    },
+    std::net::Ipv6Addr,
+};
+
+const SOURCE: Ipv6Addr = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x1234);
+// Type, code, checksum, reserved, then a source link layer address option
+const RS_SLLA: &[u8] = &[133, 0, 0x12, 0x34, 0, 0, 0, 0, 1, 1, 0x02, 0, 0, 0, 0x12, 0x34];
+
+// RS_SLLA from SOURCE to all-routers with the correct checksum
+fn packet() -> Vec<u8> {
+    let mut out = vec![
+        // ipv6
+        0x60,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x10,
+        0x3a,
+        0xff,
+    ];
+    out.extend_from_slice(&SOURCE.octets());
+    out.extend_from_slice(&Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 2).octets());
+    out.extend_from_slice(RS_SLLA);
+    out[42 .. 44].copy_from_slice(&[0x55, 0xc6]);
+    return out;
+}
+
+#[test]
+fn test_valid() {
+    assert!(valid_rs(SOURCE, 255, RS_SLLA));
+    assert!(valid_rs(SOURCE, 255, &RS_SLLA[.. 8]));
+    assert!(valid_rs(Ipv6Addr::UNSPECIFIED, 255, &RS_SLLA[.. 8]));
+}
+
+#[test]
+fn test_forwarded() {
+    assert!(!valid_rs(SOURCE, 254, RS_SLLA));
+}
+
+#[test]
+fn test_short() {
+    assert!(!valid_rs(SOURCE, 255, &RS_SLLA[.. 7]));
+}
+
+#[test]
+fn test_nonzero_code() {
+    let mut message = RS_SLLA.to_vec();
+    message[1] = 1;
+    assert!(!valid_rs(SOURCE, 255, &message));
+}
+
+#[test]
+fn test_bad_option_length() {
+    let mut message = RS_SLLA.to_vec();
+    message[9] = 0;
+    assert!(!valid_rs(SOURCE, 255, &message));
+    message[9] = 2;
+    assert!(!valid_rs(SOURCE, 255, &message));
+    assert!(!valid_rs(SOURCE, 255, &RS_SLLA[.. 12]));
+}
+
+#[test]
+fn test_unspecified_source_slla() {
+    assert!(!valid_rs(Ipv6Addr::UNSPECIFIED, 255, RS_SLLA));
+}
+
+#[test]
+fn test_parse() {
+    let solicitation = parse_solicitation(&packet()).unwrap();
+    assert_eq!(solicitation.source, SOURCE);
+    assert_eq!(solicitation.hop_limit, 255);
+    assert_eq!(solicitation.message, packet()[40..]);
+    assert!(valid_rs(solicitation.source, solicitation.hop_limit, &solicitation.message));
+}
+
+#[test]
+fn test_parse_padding() {
+    let mut padded = packet();
+    padded.extend_from_slice(&[0; 8]);
+    assert_eq!(parse_solicitation(&padded).unwrap().message, packet()[40..]);
+}
+
+#[test]
+fn test_parse_bad_checksum() {
+    let mut packet = packet();
+    packet[43] ^= 1;
+    assert!(parse_solicitation(&packet).is_none());
+}
+
+#[test]
+fn test_parse_not_rs() {
+    // RA type, checksum adjusted to match
+    let mut ra = packet();
+    ra[40] = 134;
+    ra[42] -= 1;
+    assert!(parse_solicitation(&ra).is_none());
+    assert!(parse_solicitation(&packet()[.. 47]).is_none());
+}
@@ -0,0 +1,361 @@
+use {
+    crate::{
+        announcelib::{
+            bpf,
+            port_ifindex,
+            PortReceiver,
+            ETH_P_IPV6,
+        },
+        manglelib::{
+            checked_udp,
+            dhcpv6::{
+                Dhcpv6Header,
+                Dhcpv6Message,
+                Dhcpv6Option,
+                MSG_INFORMATION_REQUEST,
+                MSG_REPLY,
+                OPT_CLIENTID,
+                OPT_IA_NA,
+                OPT_IA_PD,
+                OPT_IA_TA,
+                OPT_SERVERID,
+            },
+            encode_dns_names,
+            ipv6::{
+                IPV6_FIXED_HEADER_SIZE,
+                NEXT_HEADER_UDP,
+                UDP_PORT_DHCPV6_SERVER,
+            },
+            ModifyConfig,
+        },
+    },
+    loga::{
+        ea,
+        ResultContext,
+    },
+    std::{
+        collections::VecDeque,
+        fs,
+        io,
+        mem,
+        net::{
+            Ipv6Addr,
+            SocketAddr,
+            SocketAddrV6,
+            UdpSocket,
+        },
+        os::fd::AsRawFd,
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+#[cfg(test)]
+mod test_information;
+
+// * https://datatracker.ietf.org/doc/html/rfc8415#section-11.4
+const DUID_TYPE_LL: u16 = 3;
+const HARDWARE_TYPE_ETHERNET: u16 = 1;
+// * https://datatracker.ietf.org/doc/html/rfc8415#section-7.6
+pub const IRT_MINIMUM: u32 = 600;
+/// Requests beyond this many waiting for upstream are dropped, so a flood can't
+/// grow the queue without bound.
+const PENDING_REQUESTS_MAX: usize = 64;
+/// Big enough for any IPv6 packet without jumbograms, so messages are never
+/// truncated.
+const RECV_BUFFER_SIZE: usize = 65536;
+const UDP_HEADER_SIZE: usize = 8;
+/// How long to wait for an upstream server to answer a request first. Under
+/// INF_TIMEOUT, so the reply still arrives before the client retransmits.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc8415#section-7.6
+pub const UPSTREAM_REPLY_WAIT: Duration = Duration::from_millis(500);
+
+/// What to answer Information-Requests with, besides the resolvers and the search
+/// domains and captive portal from the rewriting config.
+pub struct InformationConfig {
+    /// Sent as the server identifier
+    pub server_duid: Vec<u8>,
+    pub ntp_servers: Vec<Ipv6Addr>,
+    /// Seconds
+    pub information_refresh_time: u32,
+}
+
+/// A link layer address DUID, stable as long as the interface MAC is.
+pub fn duid_ll(mac: [u8; 6]) -> Vec<u8> {
+    let mut out = vec![];
+    out.extend_from_slice(&DUID_TYPE_LL.to_be_bytes());
+    out.extend_from_slice(&HARDWARE_TYPE_ETHERNET.to_be_bytes());
+    out.extend_from_slice(&mac);
+    return out;
+}
+
+/// Whether a DHCPv6 message (the UDP payload) is an Information-Request. Only
+/// checks the type, `information_reply` decides whether to answer it.
+pub fn is_information_request(message: &[u8]) -> bool {
+    return Dhcpv6Message::parse(message).is_some_and(|message| message.msg_type == MSG_INFORMATION_REQUEST);
+}
+
+/// Build the Reply to a DHCPv6 message (the UDP payload), or `None` if it's not an
+/// Information-Request or should be discarded. `ips` are the resolver addresses to
+/// advertise.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc8415#section-16.12
+/// * https://datatracker.ietf.org/doc/html/rfc8415#section-18.3.6
+pub fn information_reply(
+    request: &[u8],
+    ips: &[Ipv6Addr],
+    modify_config: &ModifyConfig,
+    config: &InformationConfig,
+) -> Option<Vec<u8>> {
+    let request = Dhcpv6Message::parse(request)?;
+    if request.msg_type != MSG_INFORMATION_REQUEST {
+        return None;
+    }
+    let Dhcpv6Header::Client { transaction_id } = request.header else {
+        return None;
+    };
+    let mut client_id = None;
+    for option in &request.options {
+        match option {
+            Dhcpv6Option::Unknown { code: OPT_SERVERID, data } if *data != config.server_duid => {
+                return None;
+            },
+            Dhcpv6Option::Unknown { code: OPT_CLIENTID, data } => {
+                client_id = Some(data.clone());
+            },
+            _ => {
+                if matches!(option.code(), OPT_IA_NA | OPT_IA_TA | OPT_IA_PD) {
+                    return None;
+                }
+            },
+        }
+    }
+    let mut options = vec![Dhcpv6Option::Unknown {
+        code: OPT_SERVERID,
+        data: config.server_duid.clone(),
+    }];
+    if let Some(client_id) = client_id {
+        options.push(Dhcpv6Option::Unknown {
+            code: OPT_CLIENTID,
+            data: client_id,
+        });
+    }
+    options.push(Dhcpv6Option::DnsServers(ips.to_vec()));
+    if let Some(search_domains) = &modify_config.search_domains {
+        options.push(Dhcpv6Option::DomainList(encode_dns_names(&search_domains.domains)?));
+    }
+    if !config.ntp_servers.is_empty() {
+        options.push(Dhcpv6Option::NtpServers(config.ntp_servers.clone()));
+    }
+    if let Some(uri) = &modify_config.captive_portal {
+        options.push(Dhcpv6Option::CaptivePortal(uri.clone()));
+    }
+    options.push(Dhcpv6Option::InformationRefreshTime(config.information_refresh_time));
+    let mut out = vec![];
+    Dhcpv6Message {
+        msg_type: MSG_REPLY,
+        header: Dhcpv6Header::Client { transaction_id: transaction_id },
+        options: options,
+    }.serialize(&mut out);
+    return Some(out);
+}
+
+/// When an upstream DHCPv6 server last replied to a client. Upstream is considered
+/// gone after `window` without replies, which should be longer than the refresh
+/// time the upstream server gives clients, since they only ask that often.
+pub struct UpstreamDhcpv6 {
+    window: Duration,
+    last_reply: Option<Instant>,
+}
+
+impl UpstreamDhcpv6 {
+    pub fn new(window: Duration) -> Self {
+        return UpstreamDhcpv6 {
+            window: window,
+            last_reply: None,
+        };
+    }
+
+    /// Record an upstream reply. Returns true if upstream wasn't present before.
+    pub fn seen(&mut self, now: Instant) -> bool {
+        let was_present = self.present(now);
+        self.last_reply = Some(now);
+        return !was_present;
+    }
+
+    pub fn present(&self, now: Instant) -> bool {
+        return self.last_reply.is_some_and(|last_reply| now.saturating_duration_since(last_reply) < self.window);
+    }
+}
+
+pub struct PendingRequest {
+    pub deadline: Instant,
+    pub client: SocketAddr,
+    pub request: Vec<u8>,
+}
+
+/// Information-Requests waiting for an upstream server to answer first, in
+/// deadline order.
+#[derive(Default)]
+pub struct PendingRequests {
+    requests: VecDeque<PendingRequest>,
+}
+
+impl PendingRequests {
+    /// Answer `request` from `client` at `now` + `UPSTREAM_REPLY_WAIT` unless
+    /// upstream replies meanwhile.
+    pub fn push(&mut self, now: Instant, client: SocketAddr, request: Vec<u8>) {
+        if self.requests.len() >= PENDING_REQUESTS_MAX {
+            return;
+        }
+        self.requests.push_back(PendingRequest {
+            deadline: now + UPSTREAM_REPLY_WAIT,
+            client: client,
+            request: request,
+        });
+    }
+
+    /// When the next request is due.
+    pub fn next(&self) -> Option<Instant> {
+        return self.requests.front().map(|pending| pending.deadline);
+    }
+
+    /// Remove and return the requests due at `now`.
+    pub fn take_due(&mut self, now: Instant) -> Vec<PendingRequest> {
+        let mut out = vec![];
+        while self.requests.front().is_some_and(|pending| pending.deadline <= now) {
+            out.push(self.requests.pop_front().unwrap());
+        }
+        return out;
+    }
+}
+
+/// A message to the DHCPv6 server port, without the IPv6 and UDP headers.
+pub struct ClientMessage {
+    /// The client's address and port, replies go here
+    pub source: SocketAddrV6,
+    pub message: Vec<u8>,
+}
+
+/// Split an IPv6 packet into a `ClientMessage` if it's UDP to the DHCPv6 server
+/// port with a correct checksum, without parsing the message itself.
+pub fn parse_client_message(packet: &[u8]) -> Option<ClientMessage> {
+    if packet.first()? >> 4 != 6 {
+        return None;
+    }
+    let segment = checked_udp(packet)?;
+    if u16::from_be_bytes([segment[2], segment[3]]) != UDP_PORT_DHCPV6_SERVER {
+        return None;
+    }
+    let length = u16::from_be_bytes([segment[4], segment[5]]) as usize;
+    if length < UDP_HEADER_SIZE || length > segment.len() {
+        return None;
+    }
+    return Some(ClientMessage {
+        source: SocketAddrV6::new(
+            Ipv6Addr::from(<[u8; 16]>::try_from(&packet[8 .. 24]).unwrap()),
+            u16::from_be_bytes([segment[0], segment[1]]),
+            0,
+            0,
+        ),
+        message: segment[UDP_HEADER_SIZE .. length].to_vec(),
+    });
+}
+
+/// Receives DHCPv6 client messages on bridge ports, ignoring ones from other
+/// interfaces like the upstream side of the bridge, and sends replies.
+pub struct Dhcpv6Socket {
+    receiver: PortReceiver,
+    sender: UdpSocket,
+    /// Port interface index, and the interface index to reach clients on that port
+    /// through (the bridge, or the port itself if it's not in one)
+    scopes: Vec<(i32, u32)>,
+}
+
+impl Dhcpv6Socket {
+    pub fn open(ports: &[&str]) -> Result<Self, loga::Error> {
+        let mut scopes = vec![];
+        for port in ports {
+            let ifindex = port_ifindex(port)?;
+            let master_path = format!("/sys/class/net/{}/master/ifindex", port);
+            let scope = match fs::read_to_string(&master_path) {
+                Ok(master) => master
+                    .trim()
+                    .parse::<u32>()
+                    .context_with("Invalid bridge interface index", ea!(path = master_path))?,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => ifindex as u32,
+                Err(e) => return Err(e).context_with("Error reading port bridge", ea!(path = master_path)),
+            };
+            scopes.push((ifindex, scope));
+        }
+
+        // Keep only IPv6 with UDP directly after the fixed header and to the server
+        // port. Everything is checked again after receiving.
+        let mut filter = [
+            bpf(libc::BPF_LD | libc::BPF_H | libc::BPF_ABS, (libc::SKF_AD_OFF + libc::SKF_AD_PROTOCOL) as u32, 0, 0),
+            bpf(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, ETH_P_IPV6 as u32, 0, 5),
+            bpf(libc::BPF_LD | libc::BPF_B | libc::BPF_ABS, 6, 0, 0),
+            bpf(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, NEXT_HEADER_UDP as u32, 0, 3),
+            bpf(libc::BPF_LD | libc::BPF_H | libc::BPF_ABS, IPV6_FIXED_HEADER_SIZE as u32 + 2, 0, 0),
+            bpf(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, UDP_PORT_DHCPV6_SERVER as u32, 0, 1),
+            bpf(libc::BPF_RET | libc::BPF_K, u32::MAX, 0, 0),
+            bpf(libc::BPF_RET | libc::BPF_K, 0, 0, 0),
+        ];
+        let receiver = PortReceiver::open(ports, &mut filter)?;
+
+        // Only for sending replies from the server port. Drop everything it
+        // receives, requests come through `receiver`.
+        let sender =
+            UdpSocket::bind((Ipv6Addr::UNSPECIFIED, UDP_PORT_DHCPV6_SERVER)).context("Error binding DHCPv6 socket")?;
+        let mut filter = [bpf(libc::BPF_RET | libc::BPF_K, 0, 0, 0)];
+        let program = libc::sock_fprog {
+            len: filter.len() as u16,
+            filter: filter.as_mut_ptr(),
+        };
+        let res = unsafe {
+            libc::setsockopt(
+                sender.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_ATTACH_FILTER,
+                &program as *const libc::sock_fprog as *const libc::c_void,
+                mem::size_of::<libc::sock_fprog>() as libc::socklen_t,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error()).context("Error attaching DHCPv6 socket filter");
+        }
+        return Ok(Dhcpv6Socket {
+            receiver: receiver,
+            sender: sender,
+            scopes: scopes,
+        });
+    }
+
+    /// Wait up to `timeout` (forever if `None`) for a client message. Returns the
+    /// client address, scoped to reach it through the port it arrived on, and the
+    /// message. Returns `None` on timeout, or if the packet was sent by this host,
+    /// received on another interface, or not a valid UDP datagram to the server
+    /// port.
+    pub fn recv(&self, timeout: Option<Duration>) -> Result<Option<(SocketAddr, Vec<u8>)>, io::Error> {
+        let Some((ifindex, packet)) = self.receiver.recv(timeout, RECV_BUFFER_SIZE)? else {
+            return Ok(None);
+        };
+        let Some(mut client_message) = parse_client_message(&packet) else {
+            return Ok(None);
+        };
+        let Some((_, scope)) = self.scopes.iter().find(|(port, _)| *port == ifindex) else {
+            return Ok(None);
+        };
+        client_message.source.set_scope_id(*scope);
+        return Ok(Some((SocketAddr::V6(client_message.source), client_message.message)));
+    }
+
+    /// Replies go to the client's source address and port.
+    pub fn send(&self, message: &[u8], destination: SocketAddr) -> Result<(), io::Error> {
+        self.sender.send_to(message, destination)?;
+        return Ok(());
+    }
+}
@@ -0,0 +1,305 @@
+use {
+    super::{
+        duid_ll,
+        information_reply,
+        is_information_request,
+        parse_client_message,
+        InformationConfig,
+        PendingRequests,
+        UpstreamDhcpv6,
+        UPSTREAM_REPLY_WAIT,
+    },
+    crate::manglelib::{
+        ModifyConfig,
+        SearchDomainsConfig,
+    },
+    std::{
+        net::{
+            Ipv6Addr,
+            SocketAddr,
+            SocketAddrV6,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+const SERVER_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 0x01];
+const INFORMATION_REQUEST: &[u8] = &[
+    // Information-Request, transaction id
+    0x0b,
+    0x12,
+    0x34,
+    0x56,
+    // Client identifier, DUID-LL
+    0x00,
+    0x01,
+    0x00,
+    0x0a,
+    0x00,
+    0x03,
+    0x00,
+    0x01,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x02,
+    // Elapsed time
+    0x00,
+    0x08,
+    0x00,
+    0x02,
+    0x00,
+    0x00,
+    // Option request: DNS servers, domain list, NTP, information refresh time
+    0x00,
+    0x06,
+    0x00,
+    0x08,
+    0x00,
+    0x17,
+    0x00,
+    0x18,
+    0x00,
+    0x38,
+    0x00,
+    0x20,
+];
+
+fn config() -> InformationConfig {
+    return InformationConfig {
+        server_duid: duid_ll(SERVER_MAC),
+        ntp_servers: vec![],
+        information_refresh_time: 3600,
+    };
+}
+
+fn reply(request: &[u8], modify_config: &ModifyConfig, config: &InformationConfig) -> Option<Vec<u8>> {
+    return information_reply(request, &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)], modify_config, config);
+}
+
+#[test]
+fn test_reply() {
+    let got = reply(INFORMATION_REQUEST, &ModifyConfig::default(), &config()).unwrap();
+    let want = vec![
+        // Reply, same transaction id
+        0x07,
+        0x12,
+        0x34,
+        0x56,
+        // Server identifier
+        0x00,
+        0x02,
+        0x00,
+        0x0a,
+        0x00,
+        0x03,
+        0x00,
+        0x01,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // Client identifier
+        0x00,
+        0x01,
+        0x00,
+        0x0a,
+        0x00,
+        0x03,
+        0x00,
+        0x01,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x02,
+        // DNS servers
+        0x00,
+        0x17,
+        0x00,
+        0x10,
+        0x00,
+        0x01,
+        0x00,
+        0x02,
+        0x00,
+        0x03,
+        0x00,
+        0x04,
+        0x00,
+        0x05,
+        0x00,
+        0x06,
+        0x00,
+        0x07,
+        0x00,
+        0x08,
+        // Information refresh time
+        0x00,
+        0x20,
+        0x00,
+        0x04,
+        0x00,
+        0x00,
+        0x0e,
+        0x10,
+    ];
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_reply_options() {
+    let modify_config = ModifyConfig {
+        search_domains: Some(SearchDomainsConfig {
+            domains: vec!["lan".to_string()],
+            lifetime: None,
+        }),
+        captive_portal: Some("https://a.lan/".to_string()),
+        ..Default::default()
+    };
+    let mut config = config();
+    config.ntp_servers = vec![Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0x123)];
+    let got = reply(INFORMATION_REQUEST, &modify_config, &config).unwrap();
+
+    // After the identifiers and DNS servers
+    let got = &got[4 + 14 + 14 + 20..];
+    let mut want = vec![];
+
+    // Domain list
+    want.extend_from_slice(&[0x00, 0x18, 0x00, 0x05, 0x03, b'l', b'a', b'n', 0x00]);
+
+    // NTP server, server address suboption
+    want.extend_from_slice(&[0x00, 0x38, 0x00, 0x14, 0x00, 0x01, 0x00, 0x10]);
+    want.extend_from_slice(&config.ntp_servers[0].octets());
+
+    // Captive portal
+    want.extend_from_slice(&[0x00, 0x67, 0x00, 0x0e]);
+    want.extend_from_slice(b"https://a.lan/");
+
+    // Information refresh time
+    want.extend_from_slice(&[0x00, 0x20, 0x00, 0x04, 0x00, 0x00, 0x0e, 0x10]);
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_matching_server_id() {
+    let mut request = INFORMATION_REQUEST.to_vec();
+    request.extend_from_slice(&[0x00, 0x02, 0x00, 0x0a]);
+    request.extend_from_slice(&duid_ll(SERVER_MAC));
+    assert!(reply(&request, &ModifyConfig::default(), &config()).is_some());
+}
+
+#[test]
+fn test_other_server_id() {
+    let mut request = INFORMATION_REQUEST.to_vec();
+    request.extend_from_slice(&[0x00, 0x02, 0x00, 0x0a]);
+    request.extend_from_slice(&duid_ll([0x02, 0, 0, 0, 0, 0x03]));
+    assert!(reply(&request, &ModifyConfig::default(), &config()).is_none());
+}
+
+#[test]
+fn test_ia() {
+    // IA_NA with IAID, T1, T2
+    let mut request = INFORMATION_REQUEST.to_vec();
+    request.extend_from_slice(&[0x00, 0x03, 0x00, 0x0c]);
+    request.extend_from_slice(&[0; 12]);
+    assert!(reply(&request, &ModifyConfig::default(), &config()).is_none());
+}
+
+#[test]
+fn test_not_information_request() {
+    // Solicit
+    let mut request = INFORMATION_REQUEST.to_vec();
+    request[0] = 1;
+    assert!(reply(&request, &ModifyConfig::default(), &config()).is_none());
+    assert!(reply(&INFORMATION_REQUEST[.. 2], &ModifyConfig::default(), &config()).is_none());
+}
+
+#[test]
+fn test_upstream_window() {
+    let start = Instant::now();
+    let mut upstream = UpstreamDhcpv6::new(Duration::from_secs(100));
+    assert!(!upstream.present(start));
+    assert!(upstream.seen(start));
+    assert!(!upstream.seen(start + Duration::from_secs(50)));
+    assert!(upstream.present(start + Duration::from_secs(149)));
+
+    // Gone, then back
+    assert!(!upstream.present(start + Duration::from_secs(150)));
+    assert!(upstream.seen(start + Duration::from_secs(200)));
+    assert!(upstream.present(start + Duration::from_secs(200)));
+}
+
+#[test]
+fn test_parse_client_message() {
+    let source = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x1234);
+    let mut packet = vec![
+        // ipv6
+        0x60,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x2c,
+        0x11,
+        0x01,
+    ];
+    packet.extend_from_slice(&source.octets());
+    packet.extend_from_slice(&Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 1, 2).octets());
+    packet.extend_from_slice(&[
+        // udp, client port to server port
+        0x02,
+        0x22,
+        0x02,
+        0x23,
+        0x00,
+        0x2c,
+        0xa9,
+        0x7e,
+    ]);
+    packet.extend_from_slice(INFORMATION_REQUEST);
+    let client_message = parse_client_message(&packet).unwrap();
+    assert_eq!(client_message.source, SocketAddrV6::new(source, 546, 0, 0));
+    assert_eq!(client_message.message, INFORMATION_REQUEST);
+
+    // Bad checksum
+    let mut corrupt = packet.clone();
+    corrupt[47] ^= 1;
+    assert!(parse_client_message(&corrupt).is_none());
+
+    // To the client port, like an upstream reply
+    let mut reply = packet.clone();
+    reply[40 .. 44].copy_from_slice(&[0x02, 0x23, 0x02, 0x22]);
+    assert!(parse_client_message(&reply).is_none());
+}
+
+#[test]
+fn test_pending_requests() {
+    assert!(is_information_request(INFORMATION_REQUEST));
+    let mut solicit = INFORMATION_REQUEST.to_vec();
+    solicit[0] = 0x01;
+    assert!(!is_information_request(&solicit));
+
+    let client = SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2), 546, 0, 3));
+    let now = Instant::now();
+    let mut pending = PendingRequests::default();
+    assert_eq!(pending.next(), None);
+    pending.push(now, client, INFORMATION_REQUEST.to_vec());
+    pending.push(now + Duration::from_millis(100), client, INFORMATION_REQUEST.to_vec());
+    assert_eq!(pending.next(), Some(now + UPSTREAM_REPLY_WAIT));
+    assert!(pending.take_due(now + Duration::from_millis(499)).is_empty());
+    let due = pending.take_due(now + UPSTREAM_REPLY_WAIT);
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].client, client);
+    assert_eq!(pending.next(), Some(now + Duration::from_millis(100) + UPSTREAM_REPLY_WAIT));
+    assert_eq!(pending.take_due(now + Duration::from_secs(1)).len(), 1);
+    assert_eq!(pending.next(), None);
+}
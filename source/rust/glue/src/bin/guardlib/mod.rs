@@ -0,0 +1,217 @@
+use {
+    crate::manglelib::{
+        is_dhcpv6_reply,
+        is_ra,
+    },
+    aargvark::Aargvark,
+    std::{
+        fmt::{
+            self,
+            Display,
+        },
+        net::Ipv6Addr,
+        time::Instant,
+    },
+};
+
+#[cfg(test)]
+mod test_guard;
+
+/// Rogue sources beyond this many are only counted in aggregate, so spoofed
+/// sources can't grow the list without bound.
+const ROGUE_SOURCES_MAX: usize = 64;
+
+// RA guard: only trust RAs and DHCPv6 replies from known upstream routers.
+//
+// * https://datatracker.ietf.org/doc/html/rfc6105
+#[derive(Aargvark, Clone, Copy, Debug)]
+pub enum RaGuardMode {
+    /// Trust the first router an RA is seen from, and separately the first server a
+    /// DHCPv6 reply is seen from, unless routers are configured. Not persisted, they're
+    /// learned again after a restart.
+    Learn,
+    /// Only trust the configured routers
+    Allowlist,
+}
+
+pub fn format_mac(mac: &[u8; 6]) -> String {
+    return mac.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":");
+}
+
+pub fn parse_mac(text: &str) -> Option<[u8; 6]> {
+    let mut out = [0u8; 6];
+    let mut parts = text.split(":");
+    for b in &mut out {
+        let part = parts.next()?;
+        if part.len() != 2 {
+            return None;
+        }
+        *b = u8::from_str_radix(part, 16).ok()?;
+    }
+    if parts.next().is_some() {
+        return None;
+    }
+    return Some(out);
+}
+
+/// An upstream router, matched on whichever of the source address and MAC are set.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RouterId {
+    pub source: Option<Ipv6Addr>,
+    pub mac: Option<[u8; 6]>,
+}
+
+impl RouterId {
+    /// Parses `fe80::1`, `02:00:00:00:00:01`, or `fe80::1@02:00:00:00:00:01`.
+    pub fn parse(text: &str) -> Option<Self> {
+        if let Some((source, mac)) = text.split_once("@") {
+            return Some(RouterId {
+                source: Some(source.parse().ok()?),
+                mac: Some(parse_mac(mac)?),
+            });
+        }
+        if let Some(mac) = parse_mac(text) {
+            return Some(RouterId {
+                source: None,
+                mac: Some(mac),
+            });
+        }
+        return Some(RouterId {
+            source: Some(text.parse().ok()?),
+            mac: None,
+        });
+    }
+
+    fn matches(&self, source: Ipv6Addr, mac: Option<[u8; 6]>) -> bool {
+        return self.source.is_none_or(|s| s == source) && self.mac.is_none_or(|m| Some(m) == mac);
+    }
+}
+
+impl Display for RouterId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.source, self.mac) {
+            (Some(source), Some(mac)) => return write!(f, "{}@{}", source, format_mac(&mac)),
+            (Some(source), None) => return write!(f, "{}", source),
+            (None, Some(mac)) => return write!(f, "{}", format_mac(&mac)),
+            (None, None) => return write!(f, "any"),
+        }
+    }
+}
+
+/// What a guarded packet comes from. A separate DHCPv6 server or relay can answer
+/// before the router's first RA, so each is learned on its own.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Upstream {
+    /// Sent an RA
+    Router,
+    /// Sent a DHCPv6 reply
+    Dhcpv6Server,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RogueSource {
+    pub source: Ipv6Addr,
+    pub mac: Option<[u8; 6]>,
+    pub packets: u64,
+    pub last_seen: Instant,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum GuardResult {
+    Trusted,
+    /// Trusted, and is now the pinned router or DHCPv6 server
+    Learned(RouterId),
+    Rogue {
+        /// The first packet from this source (and MAC)
+        new: bool,
+    },
+}
+
+pub struct RouterGuard {
+    /// Trusted to send RAs, configured or learned
+    routers: Vec<RouterId>,
+    /// Trusted to send DHCPv6 replies, the same as `routers` if configured
+    dhcpv6_servers: Vec<RouterId>,
+    mode: RaGuardMode,
+    rogues: Vec<RogueSource>,
+    /// Packets from rogue sources that didn't fit in `rogues`
+    rogue_overflow_packets: u64,
+}
+
+impl RouterGuard {
+    pub fn new(mode: RaGuardMode, trusted: Vec<RouterId>) -> Self {
+        return RouterGuard {
+            routers: trusted.clone(),
+            dhcpv6_servers: trusted,
+            mode: mode,
+            rogues: vec![],
+            rogue_overflow_packets: 0,
+        };
+    }
+
+    /// Check a (valid) RA or DHCPv6 reply. `mac` is the link layer source, if known.
+    pub fn check(&mut self, upstream: Upstream, source: Ipv6Addr, mac: Option<[u8; 6]>, now: Instant) -> GuardResult {
+        let trusted = match upstream {
+            Upstream::Router => &mut self.routers,
+            Upstream::Dhcpv6Server => &mut self.dhcpv6_servers,
+        };
+        if trusted.iter().any(|r| r.matches(source, mac)) {
+            return GuardResult::Trusted;
+        }
+        if let (RaGuardMode::Learn, true) = (self.mode, trusted.is_empty()) {
+            let router = RouterId {
+                source: Some(source),
+                mac: mac,
+            };
+            trusted.push(router);
+            return GuardResult::Learned(router);
+        }
+        if let Some(rogue) = self.rogues.iter_mut().find(|r| r.source == source && r.mac == mac) {
+            rogue.packets += 1;
+            rogue.last_seen = now;
+            return GuardResult::Rogue { new: false };
+        }
+        if self.rogues.len() >= ROGUE_SOURCES_MAX {
+            self.rogue_overflow_packets += 1;
+            return GuardResult::Rogue { new: false };
+        }
+        self.rogues.push(RogueSource {
+            source: source,
+            mac: mac,
+            packets: 1,
+            last_seen: now,
+        });
+        return GuardResult::Rogue { new: true };
+    }
+
+    /// Check a packet that parsed and passed validation. Only RAs and DHCPv6 replies
+    /// are checked, anything else (Relay-Forw, or other messages sent from the DHCPv6
+    /// server port) returns `None` and is neither learned nor rogue.
+    pub fn check_packet(&mut self, payload: &[u8], mac: Option<[u8; 6]>, now: Instant) -> Option<GuardResult> {
+        let upstream = if is_ra(payload) {
+            Upstream::Router
+        } else if is_dhcpv6_reply(payload) {
+            Upstream::Dhcpv6Server
+        } else {
+            return None;
+        };
+        let source = Ipv6Addr::from(<[u8; 16]>::try_from(payload.get(8 .. 24)?).unwrap());
+        return Some(self.check(upstream, source, mac, now));
+    }
+
+    pub fn routers(&self) -> &[RouterId] {
+        return &self.routers;
+    }
+
+    pub fn dhcpv6_servers(&self) -> &[RouterId] {
+        return &self.dhcpv6_servers;
+    }
+
+    pub fn rogues(&self) -> &[RogueSource] {
+        return &self.rogues;
+    }
+
+    pub fn rogue_overflow_packets(&self) -> u64 {
+        return self.rogue_overflow_packets;
+    }
+}
@@ -0,0 +1,158 @@
+use {
+    crate::guardlib::{
+        GuardResult,
+        RaGuardMode,
+        RouterGuard,
+        RouterId,
+        Upstream,
+    },
+    std::{
+        net::Ipv6Addr,
+        time::Instant,
+    },
+};
+
+const MAC_ROUTER: [u8; 6] = [0x02, 0, 0, 0, 0, 0x01];
+const MAC_ROGUE: [u8; 6] = [0x02, 0, 0, 0, 0, 0x66];
+
+fn addr(text: &str) -> Ipv6Addr {
+    return text.parse().unwrap();
+}
+
+/// Just enough of a packet to find the upper layer, headers aren't validated here.
+fn packet(source: &str, next_header: u8, upper: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x60, 0, 0, 0];
+    out.extend_from_slice(&(upper.len() as u16).to_be_bytes());
+    out.extend_from_slice(&[next_header, 255]);
+    out.extend_from_slice(&addr(source).octets());
+    out.extend_from_slice(&addr("ff02::1").octets());
+    out.extend_from_slice(upper);
+    return out;
+}
+
+fn dhcpv6_from_server_port(source: &str, msg_type: u8) -> Vec<u8> {
+    return packet(source, 17, &[0x02, 0x23, 0x02, 0x23, 0, 12, 0, 0, msg_type, 0, 0, 0]);
+}
+
+#[test]
+fn test_parse_router() {
+    assert_eq!(RouterId::parse("fe80::1"), Some(RouterId {
+        source: Some(addr("fe80::1")),
+        mac: None,
+    }));
+    assert_eq!(RouterId::parse("02:00:00:00:00:01"), Some(RouterId {
+        source: None,
+        mac: Some(MAC_ROUTER),
+    }));
+    let both = RouterId::parse("fe80::1@02:00:00:00:00:01").unwrap();
+    assert_eq!(both, RouterId {
+        source: Some(addr("fe80::1")),
+        mac: Some(MAC_ROUTER),
+    });
+    assert_eq!(both.to_string(), "fe80::1@02:00:00:00:00:01");
+    assert_eq!(RouterId::parse("02:00:00:00:00"), None);
+    assert_eq!(RouterId::parse("fe80::1@02:00:00:00:00:0100"), None);
+    assert_eq!(RouterId::parse("router"), None);
+}
+
+#[test]
+fn test_allowlist() {
+    let now = Instant::now();
+    let router = RouterId::parse("fe80::1@02:00:00:00:00:01").unwrap();
+    let mut guard = RouterGuard::new(RaGuardMode::Allowlist, vec![router]);
+    assert_eq!(guard.check(Upstream::Router, addr("fe80::1"), Some(MAC_ROUTER), now), GuardResult::Trusted);
+
+    // Both must match
+    assert_eq!(guard.check(Upstream::Router, addr("fe80::1"), Some(MAC_ROGUE), now), GuardResult::Rogue { new: true });
+    assert_eq!(guard.check(Upstream::Router, addr("fe80::1"), None, now), GuardResult::Rogue { new: true });
+    assert_eq!(guard.check(Upstream::Router, addr("fe80::2"), Some(MAC_ROUTER), now), GuardResult::Rogue { new: true });
+    assert_eq!(
+        guard.check(Upstream::Router, addr("fe80::2"), Some(MAC_ROUTER), now),
+        GuardResult::Rogue { new: false },
+    );
+    assert_eq!(guard.rogues().len(), 3);
+    assert_eq!(guard.rogues()[2].packets, 2);
+}
+
+#[test]
+fn test_learn() {
+    let now = Instant::now();
+    let mut guard = RouterGuard::new(RaGuardMode::Learn, vec![]);
+    let router = RouterId {
+        source: Some(addr("fe80::1")),
+        mac: Some(MAC_ROUTER),
+    };
+    assert_eq!(guard.check(Upstream::Router, addr("fe80::1"), Some(MAC_ROUTER), now), GuardResult::Learned(router));
+    assert_eq!(guard.check(Upstream::Router, addr("fe80::1"), Some(MAC_ROUTER), now), GuardResult::Trusted);
+    assert_eq!(guard.check(Upstream::Router, addr("fe80::66"), Some(MAC_ROGUE), now), GuardResult::Rogue { new: true });
+    assert_eq!(guard.routers(), &[router]);
+}
+
+#[test]
+fn test_learn_packets() {
+    // Relay-Forw and Solicit sent from the server port aren't replies, so they aren't
+    // learned from or rogue
+    let now = Instant::now();
+    let mut guard = RouterGuard::new(RaGuardMode::Learn, vec![]);
+    assert_eq!(guard.check_packet(&dhcpv6_from_server_port("fe80::2", 12), None, now), None);
+    assert_eq!(guard.check_packet(&dhcpv6_from_server_port("fe80::2", 1), None, now), None);
+    assert!(guard.routers().is_empty());
+    let ra = packet("fe80::1", 58, &[134, 0, 0, 0, 64, 0, 0x07, 0x08, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert!(matches!(guard.check_packet(&ra, None, now), Some(GuardResult::Learned(_))));
+    assert_eq!(guard.check_packet(&dhcpv6_from_server_port("fe80::2", 12), None, now), None);
+    let reply = dhcpv6_from_server_port("fe80::3", 7);
+    assert_eq!(guard.check_packet(&reply, None, now), Some(GuardResult::Learned(RouterId {
+        source: Some(addr("fe80::3")),
+        mac: None,
+    })));
+    assert_eq!(guard.check_packet(&dhcpv6_from_server_port("fe80::2", 7), None, now), Some(GuardResult::Rogue {
+        new: true,
+    }));
+    assert_eq!(guard.rogues().len(), 1);
+}
+
+#[test]
+fn test_learn_dhcpv6_server_first() {
+    // A separate DHCPv6 server answering before the first RA doesn't make the router
+    // rogue, and the other way around
+    let now = Instant::now();
+    let ra = packet("fe80::1", 58, &[134, 0, 0, 0, 64, 0, 0x07, 0x08, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let reply = dhcpv6_from_server_port("fe80::2", 7);
+    let mut guard = RouterGuard::new(RaGuardMode::Learn, vec![]);
+    assert!(matches!(guard.check_packet(&reply, None, now), Some(GuardResult::Learned(_))));
+    assert!(matches!(guard.check_packet(&ra, None, now), Some(GuardResult::Learned(_))));
+    assert_eq!(guard.check_packet(&reply, None, now), Some(GuardResult::Trusted));
+    assert_eq!(guard.check_packet(&ra, None, now), Some(GuardResult::Trusted));
+
+    // Each is only trusted for what it was learned from
+    let ra_from_server = packet("fe80::2", 58, &[134, 0, 0, 0, 64, 0, 0x07, 0x08, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(guard.check_packet(&ra_from_server, None, now), Some(GuardResult::Rogue { new: true }));
+    assert_eq!(guard.routers(), &[RouterId {
+        source: Some(addr("fe80::1")),
+        mac: None,
+    }]);
+    assert_eq!(guard.dhcpv6_servers(), &[RouterId {
+        source: Some(addr("fe80::2")),
+        mac: None,
+    }]);
+}
+
+#[test]
+fn test_learn_configured() {
+    // Configured routers are trusted instead of learning
+    let now = Instant::now();
+    let mut guard = RouterGuard::new(RaGuardMode::Learn, vec![RouterId::parse("fe80::1").unwrap()]);
+    assert_eq!(guard.check(Upstream::Router, addr("fe80::66"), Some(MAC_ROGUE), now), GuardResult::Rogue { new: true });
+    assert_eq!(guard.check(Upstream::Router, addr("fe80::1"), Some(MAC_ROGUE), now), GuardResult::Trusted);
+}
+
+#[test]
+fn test_rogue_overflow() {
+    let now = Instant::now();
+    let mut guard = RouterGuard::new(RaGuardMode::Allowlist, vec![RouterId::parse("fe80::1").unwrap()]);
+    for i in 0 .. 100u16 {
+        guard.check(Upstream::Router, Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 1, i), None, now);
+    }
+    assert_eq!(guard.rogues().len(), 64);
+    assert_eq!(guard.rogue_overflow_packets(), 36);
+}
@@ -3,24 +3,89 @@ use {
         vark,
         Aargvark,
     },
-    glue::unstable_ip::UnstableIpv6,
+    addrlib::{
+        watch_resolver_addresses,
+        LinkLocalPolicy,
+    },
+    announcelib::{
+        rs::{
+            valid_rs,
+            RsSocket,
+        },
+        PacketSocket,
+        RaCache,
+        RaSchedule,
+        MAX_RA_DELAY_TIME,
+    },
+    dhcpv6lib::{
+        duid_ll,
+        information_reply,
+        is_information_request,
+        Dhcpv6Socket,
+        InformationConfig,
+        PendingRequests,
+        UpstreamDhcpv6,
+        IRT_MINIMUM,
+    },
+    guardlib::{
+        parse_mac,
+        GuardResult,
+        RaGuardMode,
+        RouterGuard,
+        RouterId,
+    },
     loga::{
+        ea,
         fatal,
+        DebugDisplay,
+        Log,
         ResultContext,
     },
-    manglelib::modify,
-    network_interface::{
-        NetworkInterface,
-        NetworkInterfaceConfig,
+    manglelib::{
+        encode_dns_names,
+        ipv6::IPV6_ALL_NODES,
+        is_dhcpv6_reply,
+        maybe_ra_or_dhcpv6_server,
+        ra_router_lifetime,
+        modify,
+        ra::{
+            RouterPreference,
+            CAPTIVE_PORTAL_MAX_URI_SIZE,
+        },
+        unsolicited_ra,
+        ModifyConfig,
+        ModifyError,
+        ModifyReport,
+        PacketTrace,
+        PioConfig,
+        Pref64Config,
+        RaHeaderConfig,
+        RouteConfig,
+        SearchDomainsConfig,
+    },
+    metricslib::{
+        Counters,
+        Metrics,
+        PolicyCounts,
     },
     nfq::{
         Queue,
         Verdict,
     },
+    rand::Rng,
     std::{
+        fs,
+        io,
+        mem,
+        net::{
+            Ipv6Addr,
+            SocketAddr,
+        },
+        ops::RangeInclusive,
         panic,
         process,
         sync::{
+            mpsc,
             Arc,
             Mutex,
         },
@@ -28,28 +93,867 @@ use {
             sleep,
             spawn,
         },
-        time::Duration,
+        time::{
+            Duration,
+            Instant,
+        },
     },
 };
 
 mod manglelib;
+mod addrlib;
+mod metricslib;
+mod guardlib;
+mod pcaplib;
+mod announcelib;
+mod dhcpv6lib;
+
+#[derive(Aargvark, Clone, Copy, Debug)]
+enum PacketPolicy {
+    /// Drop the packet
+    Drop,
+    /// Forward the packet unmodified
+    Accept,
+}
+
+#[derive(Aargvark, Clone, Copy, Debug)]
+enum FlagPolicy {
+    /// Set the flag
+    Set,
+    /// Clear the flag
+    Clear,
+    /// Leave the flag as the upstream router set it
+    Keep,
+}
+
+impl FlagPolicy {
+    fn set(self) -> Option<bool> {
+        match self {
+            FlagPolicy::Set => return Some(true),
+            FlagPolicy::Clear => return Some(false),
+            FlagPolicy::Keep => return None,
+        }
+    }
+}
 
 #[derive(Aargvark)]
 struct Args {
-    /// Name of address to get ipv6 address from to add to RDNSS
-    #[vark(flag = "--interface")]
-    interface: String,
-    /// How often (seconds) to recheck the interface for a new IP. Defaults to 60s.
-    recheck_period: Option<u64>,
-    /// Which netfilter queue to read from
-    #[vark(flag = "--nf-queue")]
-    nf_queue: u16,
+    /// Name of interface to get ipv6 addresses from to add to RDNSS. Stable global
+    /// addresses are preferred, and ULAs are always included. Either this or
+    /// `--resolvers` is required.
+    interface: Option<String>,
+    /// Comma separated resolver addresses to advertise, instead of getting them from
+    /// `--interface`.
+    resolvers: Option<String>,
+    /// Whether to also advertise a link-local address as a resolver. Defaults to
+    /// exclude.
+    link_local_resolver: Option<LinkLocalPolicy>,
+    /// When the addresses on `--interface` change, send a rewritten copy of each
+    /// upstream router's most recent RA on these comma separated LAN-facing bridge
+    /// ports (like `eth1,wlan0`), with a zero lifetime RDNSS for the old addresses, so
+    /// clients switch resolvers without waiting for the next upstream RA. Only RAs
+    /// that get an RDNSS are sent. The RAs come from the upstream router's address, so
+    /// they must not reach it: list only LAN ports, bridges are refused.
+    announce_ports: Option<String>,
+    /// Answer router solicitations received on these comma separated LAN-facing
+    /// bridge ports with rewritten copies of the cached upstream RAs, sent on the same
+    /// ports, so new clients don't wait for the next upstream RA. Solicitations from
+    /// the upstream side are left to the upstream router. Responses are rate limited
+    /// per RFC 4861. Like `--announce-ports`, bridges are refused.
+    rs_ports: Option<String>,
+    /// Answer DHCPv6 Information-Requests received on these comma separated LAN-facing
+    /// bridge ports with the resolvers, search domains, captive portal and
+    /// `--dhcpv6-ntp-servers`, while no upstream DHCPv6 server is replying (see
+    /// `--dhcpv6-upstream-window`). Each request first waits briefly so a live
+    /// upstream server answers instead. Clients ask because of the O flag, and some
+    /// stall if nothing answers. Requests from the upstream side are left alone. Like
+    /// `--rs-ports`, bridges are refused.
+    dhcpv6_ports: Option<String>,
+    /// Comma separated NTP server addresses to include in `--dhcpv6-ports`
+    /// replies.
+    dhcpv6_ntp_servers: Option<String>,
+    /// How long (seconds) clients should wait before asking `--dhcpv6-ports`
+    /// again, at least 600. Defaults to 3600, so resolver changes are picked up within
+    /// an hour.
+    dhcpv6_information_refresh_time: Option<u32>,
+    /// How long (seconds) after the last upstream DHCPv6 reply before
+    /// `--dhcpv6-ports` starts answering again. Upstream replies are only seen
+    /// when clients ask, so this should be longer than the upstream information
+    /// refresh time. Defaults to 86400, the RFC 8415 default refresh time.
+    dhcpv6_upstream_window: Option<u64>,
+    /// Which netfilter queue to read from, or an inclusive range like `0-3` to read
+    /// from several queues (`queue num 0-3 fanout`) with a worker thread per queue.
+    /// Required unless replaying a capture.
+    nf_queue: Option<String>,
+    /// Maximum number of packets the kernel holds for each queue waiting for a
+    /// verdict. Defaults to the kernel default (1024).
+    nf_queue_max_len: Option<u32>,
+    /// What the kernel should do with packets when a queue is full. Defaults to drop.
+    on_queue_full: Option<PacketPolicy>,
     /// Mark packets after modification - you must use this in your nftables rule to
-    /// prevent re-processing the same packet (feedback loop).
-    #[vark(flag = "--nf-mark")]
-    nf_mark: u32,
+    /// prevent re-processing the same packet (feedback loop). Required unless
+    /// replaying a capture.
+    nf_mark: Option<u32>,
     /// Override/inject RA MTU
     mtu: Option<u32>,
+    /// Override/inject RA PREF64 (NAT64 prefix), like `64:ff9b::/96`. The prefix
+    /// length must be one of 96, 64, 56, 48, 40, or 32.
+    pref64: Option<String>,
+    /// Lifetime (seconds) of the injected PREF64. Defaults to 1800, not the RA's
+    /// router lifetime, which is 0 for non-default routers.
+    pref64_lifetime: Option<u32>,
+    /// Comma separated prefixes like `64:ff9b::/96` to inject as RA route
+    /// information. Clients route these via the RA's source router even when it's not
+    /// their default router.
+    routes: Option<String>,
+    /// Preference of the injected routes. Defaults to `medium`.
+    route_preference: Option<RouterPreference>,
+    /// Lifetime (seconds) of the injected routes. Defaults to 1800, not the RA's
+    /// router lifetime, which is 0 for non-default routers.
+    route_lifetime: Option<u32>,
+    /// Comma separated search domains to override/inject as RA DNSSL and DHCPv6
+    /// domain list.
+    search_domains: Option<String>,
+    /// Lifetime (seconds) of the injected RA DNSSL. Defaults to 1800, not the RA's
+    /// router lifetime, which is 0 for non-default routers.
+    search_domains_lifetime: Option<u32>,
+    /// Captive portal API URI (RFC 8908) to override/inject in RAs and DHCPv6
+    /// replies, so clients find the info page. Must be `https`, or
+    /// `urn:ietf:params:capport:unrestricted` to tell clients there's no captive
+    /// portal.
+    captive_portal: Option<String>,
+    /// Raise nonzero RA prefix information valid lifetimes (seconds) to at least
+    /// this.
+    pio_min_valid_lifetime: Option<u32>,
+    /// Lower RA prefix information valid lifetimes (seconds) to at most this.
+    pio_max_valid_lifetime: Option<u32>,
+    /// Raise nonzero RA prefix information preferred lifetimes (seconds) to at least
+    /// this. Preferred lifetimes are never raised above the valid lifetime.
+    pio_min_preferred_lifetime: Option<u32>,
+    /// Lower RA prefix information preferred lifetimes (seconds) to at most this.
+    pio_max_preferred_lifetime: Option<u32>,
+    /// Set or clear the on-link (L) flag in RA prefix information.
+    pio_on_link: Option<FlagPolicy>,
+    /// Set or clear the autonomous address configuration (A) flag in RA prefix
+    /// information.
+    pio_autonomous: Option<FlagPolicy>,
+    /// Comma separated prefixes like `2001:db8::/32`. RA prefix information for
+    /// prefixes within these is removed.
+    pio_drop: Option<String>,
+    /// Set or clear the RA managed address configuration (M) flag. Defaults to `keep`.
+    ra_managed_flag: Option<FlagPolicy>,
+    /// Set or clear the RA other configuration (O) flag. Defaults to `set`, so clients
+    /// ask DHCPv6 for DNS servers even when addresses come from SLAAC. Use `keep` or
+    /// `--dhcpv6-ports` if nothing upstream answers DHCPv6.
+    ra_other_flag: Option<FlagPolicy>,
+    /// Override the RA router lifetime (seconds). Use 0 so clients don't use the
+    /// router as a default router.
+    ra_router_lifetime: Option<u16>,
+    /// Override the RA default router preference. Has no effect if the router lifetime
+    /// is 0.
+    ra_router_preference: Option<RouterPreference>,
+    /// Override the RA cur hop limit. 0 means unspecified.
+    ra_cur_hop_limit: Option<u8>,
+    /// Override the RA reachable time (milliseconds). 0 means unspecified.
+    ra_reachable_time: Option<u32>,
+    /// Override the RA retrans timer (milliseconds). 0 means unspecified.
+    ra_retrans_timer: Option<u32>,
+    /// Always inject RA RDNSS and DHCPv6 DNS servers, even if the upstream RA or
+    /// DHCPv6 reply has none, so this is the authoritative resolver on the LAN. This is
+    /// the RDNSS lifetime (seconds) to use when there's no upstream RDNSS to copy the
+    /// lifetime from.
+    inject_dns_lifetime: Option<u32>,
+    /// What to do with packets that can't be parsed or aren't RAs/DHCPv6. Defaults to
+    /// drop. With `--ra-guard`, ones that might still be RAs or DHCPv6 replies are
+    /// always dropped.
+    on_parse_failure: Option<PacketPolicy>,
+    /// What to do with packets that have a bad checksum or are RAs that fail RFC 4861
+    /// validation (hop limit, source address, code). Defaults to drop.
+    on_invalid: Option<PacketPolicy>,
+    /// What to do with packets received before a usable IP is found on the interface
+    /// (or after they're all lost). Defaults to drop.
+    before_ip: Option<PacketPolicy>,
+    /// Serve Prometheus metrics over HTTP on this address, like `127.0.0.1:9100` or
+    /// `[fd00::1]:9100`. Disabled if not specified.
+    metrics_bind: Option<String>,
+    /// Log a record of what was done to each packet (message type, source, options
+    /// seen, removed, and added, verdict), at most this many per second.
+    trace_packets: Option<u32>,
+    /// Instead of reading packets from netfilter, process the IPv6 packets in this
+    /// pcap or pcapng file and exit. Requires `--pcap-out` and `--resolvers`.
+    pcap_in: Option<String>,
+    /// Write the processed packets from `--pcap-in` to this file (pcap, raw IP link
+    /// type). Dropped packets are omitted.
+    pcap_out: Option<String>,
+    /// Only trust RAs and DHCPv6 replies from known upstream routers. Disabled if not
+    /// specified.
+    ra_guard: Option<RaGuardMode>,
+    /// Comma separated trusted routers for `--ra-guard`, each a link-local source
+    /// address, a MAC, or both like `fe80::1@02:00:00:00:00:01`. They're trusted for
+    /// both RAs and DHCPv6 replies, so include a separate DHCPv6 server or relay.
+    ra_guard_routers: Option<String>,
+    /// What to do with RAs and DHCPv6 replies from untrusted routers when using
+    /// `--ra-guard`. Accepted packets are forwarded unmodified, but still counted and
+    /// logged. Defaults to drop.
+    on_rogue_router: Option<PacketPolicy>,
+}
+
+const COUNTERS_LOG_PERIOD: Duration = Duration::from_secs(60);
+const INFORMATION_REFRESH_TIME_DEFAULT: u32 = 3600;
+// * https://datatracker.ietf.org/doc/html/rfc8415#section-7.6 (IRT_DEFAULT)
+const UPSTREAM_DHCPV6_WINDOW_DEFAULT: Duration = Duration::from_secs(86400);
+const BATCH_MAX: usize = 64;
+// * https://datatracker.ietf.org/doc/html/rfc8910#section-2
+const CAPTIVE_PORTAL_UNRESTRICTED: &str = "urn:ietf:params:capport:unrestricted";
+
+/// Allows up to `per_second` events each second.
+struct RateLimit {
+    per_second: u32,
+    window_start: Instant,
+    count: u32,
+    suppressed: u64,
+}
+
+impl RateLimit {
+    fn new(per_second: u32) -> Self {
+        return RateLimit {
+            per_second: per_second,
+            window_start: Instant::now(),
+            count: 0,
+            suppressed: 0,
+        };
+    }
+
+    /// If the event is allowed returns the number of events suppressed since the last
+    /// allowed event.
+    fn check(&mut self, now: Instant) -> Option<u64> {
+        if now.saturating_duration_since(self.window_start) >= Duration::from_secs(1) {
+            self.window_start = now;
+            self.count = 0;
+        }
+        if self.count >= self.per_second {
+            self.suppressed += 1;
+            return None;
+        }
+        self.count += 1;
+        let suppressed = self.suppressed;
+        self.suppressed = 0;
+        return Some(suppressed);
+    }
+}
+
+/// State shared by the queue workers.
+struct Shared {
+    log: Log,
+    modify_config: ModifyConfig,
+    nf_mark: u32,
+    nf_queue_max_len: Option<u32>,
+    on_queue_full: PacketPolicy,
+    on_parse_failure: PacketPolicy,
+    on_invalid: PacketPolicy,
+    on_rogue_router: PacketPolicy,
+    before_ip: PacketPolicy,
+    /// Current resolver addresses, empty if none were found
+    resolvers: Mutex<Vec<Ipv6Addr>>,
+    metrics: Arc<Mutex<Metrics>>,
+    trace_limit: Option<Mutex<RateLimit>>,
+    guard: Option<Mutex<RouterGuard>>,
+    /// Upstream RAs to send again when the resolvers change or a router solicitation
+    /// arrives, with `--announce-ports` or `--rs-ports`
+    ra_cache: Option<Mutex<RaCache>>,
+    /// Multicast RAs sent by us, for rate limiting solicited responses
+    ra_schedule: Mutex<RaSchedule>,
+    /// When a DHCPv6 reply from upstream was last seen, with `--dhcpv6-ports`
+    upstream_dhcpv6: Option<Mutex<UpstreamDhcpv6>>,
+}
+
+fn parse_queue_range_inner(text: &str) -> Result<RangeInclusive<u16>, loga::Error> {
+    let (start, end) = text.split_once("-").unwrap_or((text, text));
+    let start = start.parse::<u16>().context("Invalid queue number")?;
+    let end = end.parse::<u16>().context("Invalid queue number")?;
+    if end < start {
+        return Err(loga::err("Queue range end is less than start"));
+    }
+    return Ok(start ..= end);
+}
+
+fn parse_queue_range(text: &str) -> Result<RangeInclusive<u16>, loga::Error> {
+    return parse_queue_range_inner(text).context_with("Error parsing netfilter queue range", ea!(queue = text));
+}
+
+fn parse_ipv6_prefix_inner(text: &str) -> Result<(Ipv6Addr, u8), loga::Error> {
+    let Some((addr, length)) = text.split_once("/") else {
+        return Err(loga::err("Missing /length"));
+    };
+    let addr = addr.parse::<Ipv6Addr>().context("Invalid address")?;
+    let length = length.parse::<u8>().context("Invalid prefix length")?;
+    if length > 128 {
+        return Err(loga::err("Prefix length is greater than 128"));
+    }
+    return Ok((addr, length));
+}
+
+fn parse_ipv6_prefix(text: &str) -> Result<(Ipv6Addr, u8), loga::Error> {
+    return parse_ipv6_prefix_inner(text).context_with("Error parsing IPv6 prefix", ea!(prefix = text));
+}
+
+enum Decision {
+    /// Forward the (possibly) modified packet
+    Forward(Vec<u8>),
+    /// Forward the original packet
+    Accept,
+    Drop,
+}
+
+fn apply_policy(policy: PacketPolicy, counts: &mut PolicyCounts) -> Decision {
+    match policy {
+        PacketPolicy::Drop => {
+            counts.dropped += 1;
+            return Decision::Drop;
+        },
+        PacketPolicy::Accept => {
+            counts.accepted += 1;
+            return Decision::Accept;
+        },
+    }
+}
+
+/// Returns whether the packet is from a trusted router, or isn't an RA or DHCPv6
+/// reply. The packet must have parsed.
+fn check_router(shared: &Shared, guard: &Mutex<RouterGuard>, payload: &[u8], mac: Option<[u8; 6]>) -> bool {
+    let source = Ipv6Addr::from(<[u8; 16]>::try_from(&payload[8 .. 24]).unwrap());
+    let mut guard = guard.lock().unwrap();
+    let Some(result) = guard.check_packet(payload, mac, Instant::now()) else {
+        return true;
+    };
+    match &result {
+        GuardResult::Trusted => return true,
+        GuardResult::Learned(router) => {
+            shared.log.log_with(loga::INFO, "Learned upstream router or DHCPv6 server", ea!(router = router));
+        },
+        GuardResult::Rogue { new: true } => {
+            let router = RouterId {
+                source: Some(source),
+                mac: mac,
+            };
+            shared.log.log_with(
+                loga::WARN,
+                "Saw RA or DHCPv6 reply from untrusted router",
+                ea!(router = router, policy = shared.on_rogue_router.dbg_str()),
+            );
+        },
+        GuardResult::Rogue { new: false } => { },
+    }
+    let mut metrics = shared.metrics.lock().unwrap();
+    metrics.trusted_routers = guard.routers().to_vec();
+    metrics.trusted_dhcpv6_servers = guard.dhcpv6_servers().to_vec();
+    metrics.rogue_routers = guard.rogues().to_vec();
+    metrics.rogue_overflow_packets = guard.rogue_overflow_packets();
+    return matches!(result, GuardResult::Learned(_));
+}
+
+fn decide(
+    shared: &Shared,
+    ips: &[Ipv6Addr],
+    counters: &mut Counters,
+    payload: &[u8],
+    mac: Option<[u8; 6]>,
+) -> Decision {
+    counters.received += 1;
+    let mut report = ModifyReport::default();
+    if shared.trace_limit.is_some() {
+        report.trace = Some(PacketTrace::default());
+    }
+    let result = modify(payload, ips, &shared.modify_config, &mut report);
+
+    // RA guard, only for packets that parsed and passed validation (so forged
+    // garbage isn't learned) and are RAs or DHCPv6 replies
+    let trusted = match (&shared.guard, &result) {
+        (Some(guard), Ok(_) | Err(ModifyError::NoIp | ModifyError::Config)) => {
+            check_router(shared, guard, payload, mac)
+        },
+        _ => true,
+    };
+
+    // Remember upstream RAs to rewrite and send again if the resolvers change
+    if let (Some(ra_cache), true, Ok(_) | Err(ModifyError::NoIp | ModifyError::Config)) =
+        (&shared.ra_cache, trusted, &result) {
+        if let Some(router_lifetime) = ra_router_lifetime(payload) {
+            let source = Ipv6Addr::from(<[u8; 16]>::try_from(&payload[8 .. 24]).unwrap());
+            ra_cache.lock().unwrap().record(source, payload, router_lifetime, Instant::now());
+        }
+    }
+
+    // Stop answering Information-Requests while something upstream does
+    if let (Some(upstream_dhcpv6), true, Ok(_) | Err(ModifyError::NoIp | ModifyError::Config)) =
+        (&shared.upstream_dhcpv6, trusted, &result) {
+        if is_dhcpv6_reply(payload) && upstream_dhcpv6.lock().unwrap().seen(Instant::now()) {
+            shared.log.log(loga::INFO, "Saw upstream DHCPv6 reply, no longer answering Information-Requests");
+        }
+    }
+    let outcome;
+    let decision;
+    match result {
+        _ if !trusted => {
+            decision = apply_policy(shared.on_rogue_router, &mut counters.rogue_router);
+            outcome = format!("{:?} (rogue router)", shared.on_rogue_router);
+        },
+        Ok(ipv6_packet) => {
+            if ipv6_packet == payload {
+                counters.unchanged += 1;
+                outcome = "unchanged".to_string();
+            } else {
+                counters.rewritten += 1;
+                outcome = "rewritten".to_string();
+            }
+            counters.add_report(&report);
+            decision = Decision::Forward(ipv6_packet);
+        },
+        Err(e) => {
+            let (mut policy, counts) = match e {
+                ModifyError::NoIp => (shared.before_ip, &mut counters.no_ip),
+                // Bad, not a real packet, or undocumented headers or other issues
+                ModifyError::Parse => (shared.on_parse_failure, &mut counters.parse_error),
+                ModifyError::Invalid => (shared.on_invalid, &mut counters.invalid),
+                ModifyError::NotRa => (shared.on_parse_failure, &mut counters.not_ra),
+                ModifyError::NotDhcpv6 => (shared.on_parse_failure, &mut counters.not_dhcpv6),
+                ModifyError::Config => (shared.on_parse_failure, &mut counters.config_error),
+            };
+
+            // The guard couldn't check these, so don't let RAs or DHCPv6 replies past it
+            // by being malformed or fragmented
+            if shared.guard.is_some() &&
+                matches!(
+                    e,
+                    ModifyError::Parse | ModifyError::Invalid | ModifyError::NotRa | ModifyError::NotDhcpv6
+                ) &&
+                maybe_ra_or_dhcpv6_server(payload) {
+                policy = PacketPolicy::Drop;
+            }
+            decision = apply_policy(policy, counts);
+            outcome = format!("{:?} ({:?})", policy, e);
+        },
+    }
+
+    // Trace
+    if let (Some(trace_limit), Some(trace)) = (&shared.trace_limit, report.trace) {
+        let Some(suppressed) = trace_limit.lock().unwrap().check(Instant::now()) else {
+            return decision;
+        };
+        if suppressed > 0 {
+            shared.log.log_with(loga::INFO, "Suppressed packet traces", ea!(count = suppressed));
+        }
+        let source = payload.get(8 .. 24).map(|s| Ipv6Addr::from(<[u8; 16]>::try_from(s).unwrap()));
+        shared.log.log_with(
+            loga::INFO,
+            "Packet",
+            ea!(
+                source = source.dbg_str(),
+                message = trace.message,
+                options_seen = trace.options_seen.dbg_str(),
+                options_removed = trace.options_removed.dbg_str(),
+                options_added = trace.options_added.dbg_str(),
+                verdict = outcome
+            ),
+        );
+    }
+    return decision;
+}
+
+/// Send rewritten copies of the cached upstream RAs on each port, returning the
+/// routers whose RAs were sent. With `require_rdnss`, RAs that didn't get an RDNSS
+/// are skipped.
+fn send_cached_ras(
+    shared: &Shared,
+    ra_cache: &Mutex<RaCache>,
+    sockets: &[PacketSocket],
+    ips: &[Ipv6Addr],
+    withdrawn: &[Ipv6Addr],
+    require_rdnss: bool,
+) -> Vec<Ipv6Addr> {
+    let now = Instant::now();
+    let ras =
+        ra_cache
+            .lock()
+            .unwrap()
+            .fresh(now)
+            .map(|cached| (cached.source, cached.packet.clone()))
+            .collect::<Vec<_>>();
+    let mut sent = vec![];
+    for (router, packet) in ras {
+        let mut report = ModifyReport::default();
+        let packet = match unsolicited_ra(&packet, ips, withdrawn, &shared.modify_config, &mut report) {
+            Ok(packet) => packet,
+            Err(e) => {
+                shared.log.log_with(
+                    loga::WARN,
+                    "Error rewriting cached RA",
+                    ea!(router = router, error = e.dbg_str()),
+                );
+                continue;
+            },
+        };
+        if require_rdnss && !report.rdnss {
+            continue;
+        }
+        let mut sent_any = false;
+        for socket in sockets {
+            if let Err(e) =
+                socket
+                    .send_multicast(&packet, IPV6_ALL_NODES)
+                    .context_with("Error sending RA", ea!(router = router, port = socket.interface())) {
+                shared.log.log_err(loga::WARN, e);
+                continue;
+            }
+            sent_any = true;
+        }
+        if sent_any {
+            sent.push(router);
+        }
+    }
+    if !sent.is_empty() {
+        shared.ra_schedule.lock().unwrap().sent(now);
+    }
+    return sent;
+}
+
+/// Send rewritten copies of the cached upstream RAs, so clients switch resolvers
+/// without waiting for the next upstream RA.
+fn announce(
+    shared: &Shared,
+    ra_cache: &Mutex<RaCache>,
+    sockets: &[PacketSocket],
+    ips: &[Ipv6Addr],
+    withdrawn: &[Ipv6Addr],
+) {
+    for router in send_cached_ras(shared, ra_cache, sockets, ips, withdrawn, true) {
+        shared.metrics.lock().unwrap().unsolicited_ras += 1;
+        shared.log.log_with(loga::INFO, "Sent unsolicited RA", ea!(router = router));
+    }
+}
+
+/// Answer router solicitations with the cached upstream RAs, multicast after a
+/// random delay, with solicitations arriving meanwhile sharing the response. Only
+/// returns on error.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc4861#section-6.2.6
+fn run_rs_responder(
+    shared: &Shared,
+    ra_cache: &Mutex<RaCache>,
+    rs_socket: &RsSocket,
+    sockets: &[PacketSocket],
+) -> Result<(), loga::Error> {
+    let mut rng = rand::thread_rng();
+    loop {
+        let timeout =
+            shared.ra_schedule.lock().unwrap().next().map(|next| next.saturating_duration_since(Instant::now()));
+        if let Some(solicitation) = rs_socket.recv(timeout).context("Error receiving router solicitation")? {
+            if valid_rs(solicitation.source, solicitation.hop_limit, &solicitation.message) {
+                shared.metrics.lock().unwrap().router_solicitations += 1;
+                let delay = rng.gen_range(Duration::ZERO ..= MAX_RA_DELAY_TIME);
+                shared.ra_schedule.lock().unwrap().solicited(Instant::now(), delay);
+            }
+        }
+        if !shared.ra_schedule.lock().unwrap().take_due(Instant::now()) {
+            continue;
+        }
+
+        // Before ips are found there's nothing to rewrite with, the upstream RAs are
+        // handled per policy
+        let ips = shared.resolvers.lock().unwrap().clone();
+        if ips.is_empty() {
+            continue;
+        }
+        for router in send_cached_ras(shared, ra_cache, sockets, &ips, &[], false) {
+            shared.metrics.lock().unwrap().solicited_ras += 1;
+            shared.log.log_with(loga::DEBUG, "Sent solicited RA", ea!(router = router));
+        }
+    }
+}
+
+/// Answer Information-Requests while no upstream DHCPv6 server is replying. Only
+/// returns on error.
+fn run_dhcpv6_responder(
+    shared: &Shared,
+    upstream_dhcpv6: &Mutex<UpstreamDhcpv6>,
+    socket: &Dhcpv6Socket,
+    config: &InformationConfig,
+) -> Result<(), loga::Error> {
+    let mut pending = PendingRequests::default();
+    loop {
+        let timeout = pending.next().map(|next| next.saturating_duration_since(Instant::now()));
+        if let Some((client, request)) = socket.recv(timeout).context("Error receiving DHCPv6 message")? {
+            // Give an upstream server not seen yet (or back after going away) the chance
+            // to answer first, its reply passes through the queue
+            if is_information_request(&request) && !upstream_dhcpv6.lock().unwrap().present(Instant::now()) {
+                pending.push(Instant::now(), client, request);
+            }
+        }
+        let due = pending.take_due(Instant::now());
+        if due.is_empty() || upstream_dhcpv6.lock().unwrap().present(Instant::now()) {
+            continue;
+        }
+
+        // Before ips are found there's nothing to answer with
+        let ips = shared.resolvers.lock().unwrap().clone();
+        if ips.is_empty() {
+            continue;
+        }
+        for pending in due {
+            let Some(reply) = information_reply(&pending.request, &ips, &shared.modify_config, config) else {
+                continue;
+            };
+            if let Err(e) =
+                socket
+                    .send(&reply, pending.client)
+                    .context_with("Error sending DHCPv6 reply", ea!(client = pending.client)) {
+                shared.log.log_err(loga::WARN, e);
+                continue;
+            }
+            shared.metrics.lock().unwrap().dhcpv6_information_replies += 1;
+        }
+    }
+}
+
+/// Handle packets from one queue. Only returns on error.
+fn run_worker(shared: &Shared, queue_num: u16) -> Result<(), loga::Error> {
+    let mut nf_queue = Queue::open().context("Error opening netfilter queue")?;
+    nf_queue.bind(queue_num).context_with("Error binding netfilter queue", ea!(queue = queue_num))?;
+    if let Some(max_len) = shared.nf_queue_max_len {
+        nf_queue
+            .set_queue_max_len(queue_num, max_len)
+            .context_with("Error setting netfilter queue max length", ea!(queue = queue_num))?;
+    }
+    if let PacketPolicy::Accept = shared.on_queue_full {
+        nf_queue
+            .set_fail_open(queue_num, true)
+            .context_with("Error enabling netfilter queue fail-open", ea!(queue = queue_num))?;
+    }
+    loop {
+        // Wait for a packet, then take any others already queued so shared state is only
+        // locked once per batch. Only receiving is batched: nfq has no batch verdict
+        // (NFQA_VERDICT_BATCH), so verdicts are still sent one message per packet.
+        nf_queue.set_nonblocking(false);
+        let mut batch = vec![nf_queue.recv().context("Error reading netfilter queue")?];
+        nf_queue.set_nonblocking(true);
+        while batch.len() < BATCH_MAX {
+            match nf_queue.recv() {
+                Ok(nf_queue_msg) => batch.push(nf_queue_msg),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e).context("Error reading netfilter queue"),
+            }
+        }
+
+        // Process
+        let ips = shared.resolvers.lock().unwrap().clone();
+        let mut counters = Counters::default();
+        for mut nf_queue_msg in batch {
+            let mac = nf_queue_msg.get_hw_addr().and_then(|a| <[u8; 6]>::try_from(a).ok());
+            match decide(shared, &ips, &mut counters, nf_queue_msg.get_payload(), mac) {
+                Decision::Forward(ipv6_packet) => {
+                    nf_queue_msg.set_payload(ipv6_packet);
+                    nf_queue_msg.set_nfmark(shared.nf_mark);
+                    nf_queue_msg.set_verdict(Verdict::Repeat);
+                },
+                Decision::Accept => nf_queue_msg.set_verdict(Verdict::Accept),
+                Decision::Drop => nf_queue_msg.set_verdict(Verdict::Drop),
+            }
+            nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
+        }
+        shared.metrics.lock().unwrap().counters.add(&counters);
+    }
+}
+
+/// The rewriting config from the command line flags, with the flag defaults.
+fn build_modify_config(args: &Args) -> Result<ModifyConfig, loga::Error> {
+    let mut pio_drop_prefixes = vec![];
+    if let Some(prefixes) = &args.pio_drop {
+        for prefix in prefixes.split(",") {
+            pio_drop_prefixes.push(parse_ipv6_prefix(prefix.trim())?);
+        }
+    }
+    let mut routes = vec![];
+    if let Some(prefixes) = &args.routes {
+        for prefix in prefixes.split(",") {
+            let (prefix, prefix_length) = parse_ipv6_prefix(prefix.trim())?;
+            routes.push(RouteConfig {
+                prefix: prefix,
+                prefix_length: prefix_length,
+                preference: args.route_preference.unwrap_or(RouterPreference::Medium),
+                lifetime: args.route_lifetime,
+            });
+        }
+    }
+    let pio_config = PioConfig {
+        min_valid_lifetime: args.pio_min_valid_lifetime,
+        max_valid_lifetime: args.pio_max_valid_lifetime,
+        min_preferred_lifetime: args.pio_min_preferred_lifetime,
+        max_preferred_lifetime: args.pio_max_preferred_lifetime,
+        on_link: args.pio_on_link.and_then(FlagPolicy::set),
+        autonomous: args.pio_autonomous.and_then(FlagPolicy::set),
+        drop_prefixes: pio_drop_prefixes,
+    };
+    let ra_header_config = RaHeaderConfig {
+        managed: args.ra_managed_flag.and_then(FlagPolicy::set),
+        other: args.ra_other_flag.unwrap_or(FlagPolicy::Set).set(),
+        router_lifetime: args.ra_router_lifetime,
+        preference: args.ra_router_preference,
+        cur_hop_limit: args.ra_cur_hop_limit,
+        reachable_time: args.ra_reachable_time,
+        retrans_timer: args.ra_retrans_timer,
+    };
+    return Ok(ModifyConfig {
+        mtu: args.mtu,
+        pref64: match &args.pref64 {
+            Some(pref64) => {
+                let (prefix, prefix_length) = parse_ipv6_prefix(pref64)?;
+                if ![96, 64, 56, 48, 40, 32].contains(&prefix_length) {
+                    return Err(
+                        loga::err_with(
+                            "PREF64 prefix length must be one of 96, 64, 56, 48, 40, 32",
+                            ea!(prefix = pref64),
+                        ),
+                    );
+                }
+                Some(Pref64Config {
+                    prefix: prefix,
+                    prefix_length: prefix_length,
+                    lifetime: args.pref64_lifetime,
+                })
+            },
+            None => None,
+        },
+        routes: routes,
+        pio: Some(pio_config).filter(|c| *c != PioConfig::default()),
+        ra_header: Some(ra_header_config).filter(|c| *c != RaHeaderConfig::default()),
+        search_domains: match &args.search_domains {
+            Some(search_domains) => {
+                let domains = search_domains.split(",").map(|d| d.trim().to_string()).collect::<Vec<_>>();
+                if encode_dns_names(&domains).is_none() {
+                    return Err(
+                        loga::err_with("Invalid search domain", ea!(search_domains = search_domains)),
+                    );
+                }
+                Some(SearchDomainsConfig {
+                    domains: domains,
+                    lifetime: args.search_domains_lifetime,
+                })
+            },
+            None => None,
+        },
+        captive_portal: match &args.captive_portal {
+            Some(uri) => {
+                // * https://datatracker.ietf.org/doc/html/rfc8908#section-2
+                if !uri.starts_with("https://") && uri != CAPTIVE_PORTAL_UNRESTRICTED {
+                    return Err(loga::err_with("Captive portal URI must be https", ea!(uri = uri)));
+                }
+                if uri.len() > CAPTIVE_PORTAL_MAX_URI_SIZE {
+                    return Err(loga::err_with("Captive portal URI is too long", ea!(uri = uri)));
+                }
+                Some(uri.clone())
+            },
+            None => None,
+        },
+        inject_dns_lifetime: args.inject_dns_lifetime,
+    });
+}
+
+/// Everything the packet handling needs from the command line flags, with the
+/// flag defaults. Doesn't open any sockets.
+fn build_shared(args: &Args, log: &Log) -> Result<Shared, loga::Error> {
+    let static_resolvers = match &args.resolvers {
+        Some(resolvers) => {
+            let mut out = vec![];
+            for resolver in resolvers.split(",") {
+                out.push(
+                    resolver
+                        .trim()
+                        .parse::<Ipv6Addr>()
+                        .context_with("Invalid resolver address", ea!(resolver = resolver))?,
+                );
+            }
+            Some(out)
+        },
+        None => None,
+    };
+    if args.interface.is_some() == static_resolvers.is_some() {
+        return Err(loga::err("Exactly one of --interface or --resolvers is required"));
+    }
+    let nf_mark = match (&args.pcap_in, args.nf_mark) {
+        // Unused when replaying
+        (Some(_), _) => 0,
+        (None, Some(nf_mark)) => nf_mark,
+        (None, None) => return Err(loga::err("--nf-mark is required unless replaying with --pcap-in")),
+    };
+    let mut trusted_routers = vec![];
+    if let Some(routers) = &args.ra_guard_routers {
+        for router in routers.split(",") {
+            let Some(router) = RouterId::parse(router.trim()) else {
+                return Err(loga::err_with("Invalid RA guard router", ea!(router = router)));
+            };
+            trusted_routers.push(router);
+        }
+    }
+    let guard = match args.ra_guard {
+        Some(RaGuardMode::Allowlist) if trusted_routers.is_empty() => {
+            return Err(loga::err("--ra-guard-routers is required with --ra-guard allowlist"));
+        },
+        Some(mode) => Some(RouterGuard::new(mode, trusted_routers.clone())),
+        None => None,
+    };
+    return Ok(Shared {
+        log: log.clone(),
+        modify_config: build_modify_config(args)?,
+        nf_mark: nf_mark,
+        nf_queue_max_len: args.nf_queue_max_len,
+        on_queue_full: args.on_queue_full.unwrap_or(PacketPolicy::Drop),
+        on_parse_failure: args.on_parse_failure.unwrap_or(PacketPolicy::Drop),
+        on_invalid: args.on_invalid.unwrap_or(PacketPolicy::Drop),
+        on_rogue_router: args.on_rogue_router.unwrap_or(PacketPolicy::Drop),
+        before_ip: args.before_ip.unwrap_or(PacketPolicy::Drop),
+        resolvers: Mutex::new(static_resolvers.clone().unwrap_or_default()),
+        metrics: Arc::new(Mutex::new(Metrics {
+            resolvers: static_resolvers.clone().unwrap_or_default(),
+            trusted_routers: trusted_routers.clone(),
+            trusted_dhcpv6_servers: trusted_routers,
+            ..Default::default()
+        })),
+        trace_limit: args.trace_packets.map(|per_second| Mutex::new(RateLimit::new(per_second))),
+        guard: guard.map(Mutex::new),
+        ra_cache: if args.announce_ports.is_some() || args.rs_ports.is_some() {
+            Some(Mutex::new(RaCache::default()))
+        } else {
+            None
+        },
+        ra_schedule: Mutex::new(RaSchedule::default()),
+        upstream_dhcpv6: args.dhcpv6_ports.as_ref().map(|_| {
+            return Mutex::new(
+                UpstreamDhcpv6::new(
+                    args
+                        .dhcpv6_upstream_window
+                        .map(Duration::from_secs)
+                        .unwrap_or(UPSTREAM_DHCPV6_WINDOW_DEFAULT),
+                ),
+            );
+        }),
+    });
+}
+
+/// Process the packets in the `--pcap-in` capture (pcap or pcapng) like queued
+/// packets, returning a pcap of the forwarded ones.
+fn replay_capture(shared: &Shared, pcap_in: &str) -> Result<Vec<u8>, loga::Error> {
+    let ips = shared.resolvers.lock().unwrap().clone();
+    let source = fs::read(pcap_in).context_with("Error reading input capture", ea!(path = pcap_in))?;
+    let mut counters = Counters::default();
+    let out = pcaplib::replay(&source, |packet| {
+        match decide(shared, &ips, &mut counters, packet, None) {
+            Decision::Forward(ipv6_packet) => return Some(ipv6_packet),
+            Decision::Accept => return Some(packet.to_vec()),
+            Decision::Drop => return None,
+        }
+    }).context_with("Error processing capture", ea!(path = pcap_in))?;
+    shared.log.log_with(loga::INFO, "Replayed capture", ea!(counters = counters.dbg_str()));
+    return Ok(out);
 }
 
 fn main() {
@@ -60,109 +964,206 @@ fn main() {
             process::exit(1);
         }));
         let args = vark::<Args>();
-        let recheck_period = args.recheck_period.unwrap_or(60);
-        let mut nf_queue = Queue::open().context("Error opening netfilter queue")?;
-        nf_queue.bind(args.nf_queue).context("Error binding netfilter queue")?;
-        let ip_rxtx = Arc::new(Mutex::new(None));
+        let log = Log::new_root(loga::INFO);
+        let shared = Arc::new(build_shared(&args, &log)?);
+
+        // Replay a capture instead of processing live packets
+        if let Some(pcap_in) = &args.pcap_in {
+            let Some(pcap_out) = &args.pcap_out else {
+                return Err(loga::err("--pcap-out is required with --pcap-in"));
+            };
+            let out = replay_capture(&shared, pcap_in)?;
+            fs::write(pcap_out, out).context_with("Error writing output capture", ea!(path = pcap_out))?;
+            return Ok(());
+        }
+        let announce_sockets = match &args.announce_ports {
+            Some(announce_ports) => {
+                if args.interface.is_none() {
+                    return Err(loga::err("--announce-ports requires --interface"));
+                }
+                let mut sockets = vec![];
+                for port in announce_ports.split(",") {
+                    sockets.push(PacketSocket::open(port.trim())?);
+                }
+                Some(sockets)
+            },
+            None => None,
+        };
+        let rs_sockets = match &args.rs_ports {
+            Some(rs_ports) => {
+                let ports = rs_ports.split(",").map(|port| port.trim()).collect::<Vec<_>>();
+                let mut sockets = vec![];
+                for port in &ports {
+                    sockets.push(PacketSocket::open(port)?);
+                }
+                Some((RsSocket::open(&ports)?, sockets))
+            },
+            None => None,
+        };
+        let dhcpv6_responder = match &args.dhcpv6_ports {
+            Some(dhcpv6_ports) => {
+                let ports = dhcpv6_ports.split(",").map(|port| port.trim()).collect::<Vec<_>>();
+                let mut ntp_servers = vec![];
+                if let Some(servers) = &args.dhcpv6_ntp_servers {
+                    for server in servers.split(",") {
+                        ntp_servers.push(
+                            server
+                                .trim()
+                                .parse::<Ipv6Addr>()
+                                .context_with("Invalid NTP server address", ea!(server = server))?,
+                        );
+                    }
+                }
+                let information_refresh_time =
+                    args.dhcpv6_information_refresh_time.unwrap_or(INFORMATION_REFRESH_TIME_DEFAULT);
+                if information_refresh_time < IRT_MINIMUM {
+                    return Err(
+                        loga::err_with(
+                            "--dhcpv6-information-refresh-time is below the minimum",
+                            ea!(minimum = IRT_MINIMUM),
+                        ),
+                    );
+                }
+                let address_path = format!("/sys/class/net/{}/address", ports[0]);
+                let address =
+                    fs::read_to_string(&address_path).context_with(
+                        "Error reading interface MAC",
+                        ea!(path = address_path),
+                    )?;
+                let Some(mac) = parse_mac(address.trim()) else {
+                    return Err(loga::err_with("Invalid interface MAC", ea!(mac = address.trim())));
+                };
+                Some((Dhcpv6Socket::open(&ports)?, InformationConfig {
+                    server_duid: duid_ll(mac),
+                    ntp_servers: ntp_servers,
+                    information_refresh_time: information_refresh_time,
+                }))
+            },
+            None => None,
+        };
+        let Some(nf_queue) = &args.nf_queue else {
+            return Err(loga::err("--nf-queue is required unless replaying with --pcap-in"));
+        };
+        let queue_range = parse_queue_range(nf_queue)?;
+
+        // Serve metrics
+        if let Some(bind) = &args.metrics_bind {
+            let bind = bind.parse::<SocketAddr>().context_with("Invalid metrics bind address", ea!(bind = bind))?;
+            spawn({
+                let log = log.clone();
+                let metrics = shared.metrics.clone();
+                move || {
+                    if let Err(e) = metricslib::serve(&log, bind, metrics) {
+                        log.log_err(loga::WARN, e.context("Metrics server failed"));
+                    }
+                }
+            });
+        }
 
-        // Wait for initial ip, or get next ip
+        // Periodically report
         spawn({
-            let ip_rxtx = ip_rxtx.clone();
-            let want_iface = args.interface;
+            let log = log.clone();
+            let metrics = shared.metrics.clone();
             move || {
-                let mut found_first = false;
+                let mut logged_counters = Counters::default();
                 loop {
-                    let mut found = None;
-                    for iface in NetworkInterface::show()
-                        .context("Failure listing network interfaces")
-                        .unwrap()
-                        .iter() {
-                        if want_iface != iface.name {
-                            continue;
-                        }
-                        for addr in &iface.addr {
-                            let std::net::IpAddr::V6(addr) = addr.ip() else {
-                                continue;
-                            };
-                            if !addr.unstable_is_global() {
-                                continue;
-                            }
-                            found = Some(addr);
-                            found_first = true;
-                        }
-                    }
-                    if found.is_none() {
-                        eprintln!("Interface not found or no global ipv6 address found on interface.");
-                    }
-                    *ip_rxtx.lock().unwrap() = Some(found);
-                    if !found_first {
-                        sleep(Duration::from_secs(5));
-                    } else {
-                        sleep(Duration::from_secs(recheck_period));
+                    sleep(COUNTERS_LOG_PERIOD);
+                    let counters = metrics.lock().unwrap().counters;
+                    if counters != logged_counters {
+                        log.log_with(loga::INFO, "Packet counters", ea!(counters = counters.dbg_str()));
+                        logged_counters = counters;
                     }
                 }
             }
         });
 
-        // Drop messages until we get an ip
-        eprintln!("Starting, waiting for first packet, then dropping packets until global IP found");
-        let (mut nf_queue_msg, mut ip) = loop {
-            let mut nf_queue_msg = nf_queue.recv().context("Error reading netfilter queue")?;
-            if let Some(Some(ip)) = ip_rxtx.lock().unwrap().take() {
-                break (nf_queue_msg, ip);
-            }
-            nf_queue_msg.set_verdict(Verdict::Drop);
-            nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
-        };
-        loop {
-            eprintln!("Found global IP {}, switching from dropping to rewriting packets", ip);
-
-            // Replace RDNSS in subsequent RAs (continue with last msg of previous loop).
-            // Until we lose the ip again.
-            loop {
-                // Modify
-                match modify(nf_queue_msg.get_payload(), ip, args.mtu) {
-                    Some(ipv6_packet) => {
-                        nf_queue_msg.set_payload(ipv6_packet);
-                        nf_queue_msg.set_nfmark(args.nf_mark);
-                        nf_queue_msg.set_verdict(Verdict::Repeat);
-                        nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
-                    },
-                    None => {
-                        // Bad, not a real packet, or undocumented headers or other issues
-                        nf_queue_msg.set_verdict(Verdict::Drop);
-                        nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
-                    },
+        // Answer router solicitations
+        if let Some((rs_socket, sockets)) = rs_sockets {
+            spawn({
+                let shared = shared.clone();
+                move || {
+                    let ra_cache = shared.ra_cache.as_ref().unwrap();
+                    if let Err(e) = run_rs_responder(&shared, ra_cache, &rs_socket, &sockets) {
+                        shared.log.log_err(loga::WARN, e.context("Router solicitation responder failed"));
+                    }
                 }
+            });
+        }
 
-                // Wait for next msg
-                nf_queue_msg = nf_queue.recv().context("Error reading netfilter queue")?;
-
-                // Check for ips changes
-                if let Some(update) = ip_rxtx.lock().unwrap().take() {
-                    match update {
-                        Some(new_ip) => {
-                            ip = new_ip;
-                        },
-                        None => {
-                            break;
-                        },
+        // Answer Information-Requests
+        if let Some((socket, config)) = dhcpv6_responder {
+            spawn({
+                let shared = shared.clone();
+                move || {
+                    let upstream_dhcpv6 = shared.upstream_dhcpv6.as_ref().unwrap();
+                    if let Err(e) = run_dhcpv6_responder(&shared, upstream_dhcpv6, &socket, &config) {
+                        shared.log.log_err(loga::WARN, e.context("DHCPv6 responder failed"));
                     }
                 }
-            }
-            eprintln!("Lost IP, switching from modifying packets to dropping them");
-
-            // Drop messages again
-            loop {
-                nf_queue_msg = nf_queue.recv().context("Error reading netfilter queue")?;
-                if let Some(Some(new_ip)) = ip_rxtx.lock().unwrap().take() {
-                    ip = new_ip;
-                    break;
+            });
+        }
+
+        // Wait for initial ips, or get next ips
+        if let Some(want_iface) = args.interface {
+            log.log(loga::INFO, "Starting, waiting for usable IPs before rewriting packets");
+            spawn({
+                let shared = shared.clone();
+                let link_local = args.link_local_resolver.unwrap_or(LinkLocalPolicy::Exclude);
+                move || {
+                    loop {
+                        let log = &shared.log;
+                        if let Err(e) = watch_resolver_addresses(log, &want_iface, link_local, |found| {
+                            let mut ips = shared.resolvers.lock().unwrap();
+                            if ips.is_empty() && !found.is_empty() {
+                                log.log_with(
+                                    loga::INFO,
+                                    "Found IPs, switching to rewriting packets",
+                                    ea!(ips = found.dbg_str()),
+                                );
+                            } else if !ips.is_empty() && found.is_empty() {
+                                log.log_with(
+                                    loga::INFO,
+                                    "Lost IPs, switching from rewriting packets to policy",
+                                    ea!(policy = shared.before_ip.dbg_str()),
+                                );
+                            } else if !found.is_empty() {
+                                log.log_with(loga::INFO, "IPs changed", ea!(ips = found.dbg_str()));
+                            }
+                            let mut metrics = shared.metrics.lock().unwrap();
+                            metrics.resolvers = found.clone();
+                            metrics.resolvers_changed_at = Some(Instant::now());
+                            drop(metrics);
+                            let withdrawn = mem::replace(&mut *ips, found.clone());
+                            drop(ips);
+
+                            // Tell clients now rather than at the next upstream RA
+                            if let (Some(sockets), Some(ra_cache)) = (&announce_sockets, &shared.ra_cache) {
+                                if !found.is_empty() && found != withdrawn {
+                                    announce(&shared, ra_cache, sockets, &found, &withdrawn);
+                                }
+                            }
+                        }) {
+                            log.log_err(loga::WARN, e.context("Error watching interface addresses, retrying"));
+                        }
+                        sleep(Duration::from_secs(5));
+                    }
                 }
-                nf_queue_msg.set_verdict(Verdict::Drop);
-                nf_queue.verdict(nf_queue_msg).context("Error setting netfilter message verdict")?;
-            };
+            });
+        }
+
+        // Rewrite messages when we have an ip, otherwise handle per policy. One worker
+        // per queue, stop if any fail.
+        let (worker_tx, worker_rx) = mpsc::channel();
+        for queue_num in queue_range {
+            spawn({
+                let shared = shared.clone();
+                let worker_tx = worker_tx.clone();
+                move || {
+                    _ = worker_tx.send(run_worker(&shared, queue_num));
+                }
+            });
         }
+        return worker_rx.recv().unwrap();
     }() {
         Ok(_) => (),
         Err(e) => fatal(e),
@@ -0,0 +1,250 @@
+use {
+    super::{
+        read_ipv6,
+        read_u16,
+    },
+    std::net::Ipv6Addr,
+};
+
+// DHCPv6
+//
+// * https://datatracker.ietf.org/doc/html/rfc8415#section-8
+const DHCPV6_FIXED_HEADER_SIZE: usize = 4;
+const DHCPV6_RELAY_FIXED_HEADER_SIZE: usize = 34;
+pub const MSG_ADVERTISE: u8 = 2;
+pub const MSG_REPLY: u8 = 7;
+pub const MSG_INFORMATION_REQUEST: u8 = 11;
+const MSG_RELAY_FORW: u8 = 12;
+pub const MSG_RELAY_REPL: u8 = 13;
+pub const OPT_CLIENTID: u16 = 1;
+pub const OPT_SERVERID: u16 = 2;
+pub const OPT_IA_NA: u16 = 3;
+pub const OPT_IA_TA: u16 = 4;
+const OPT_IAADDR: u16 = 5;
+pub const OPT_RELAY_MSG: u16 = 9;
+pub const OPT_IA_PD: u16 = 25;
+const OPT_IAPREFIX: u16 = 26;
+// * https://datatracker.ietf.org/doc/html/rfc3646#section-3
+pub const OPT_DNS_SERVERS: u16 = 23;
+pub const OPT_DOMAIN_LIST: u16 = 24;
+// * https://datatracker.ietf.org/doc/html/rfc8415#section-21.23
+pub const OPT_INFORMATION_REFRESH_TIME: u16 = 32;
+// * https://datatracker.ietf.org/doc/html/rfc5908#section-4
+pub const OPT_NTP_SERVER: u16 = 56;
+const NTP_SUBOPTION_SRV_ADDR: u16 = 1;
+// * https://datatracker.ietf.org/doc/html/rfc8910#section-2.1
+pub const OPT_CAPTIVE_PORTAL: u16 = 103;
+// Relayed messages and encapsulated options deeper than this are left unparsed to
+// bound recursion on hostile input. Well above the relay hop limit (8).
+//
+// * https://datatracker.ietf.org/doc/html/rfc8415#section-7.6
+const MAX_NESTING: usize = 32;
+
+pub struct Dhcpv6Message {
+    pub msg_type: u8,
+    pub header: Dhcpv6Header,
+    pub options: Vec<Dhcpv6Option>,
+}
+
+pub enum Dhcpv6Header {
+    Client {
+        transaction_id: [u8; 3],
+    },
+    // * https://datatracker.ietf.org/doc/html/rfc8415#section-9
+    Relay {
+        hop_count: u8,
+        link_address: Ipv6Addr,
+        peer_address: Ipv6Addr,
+    },
+}
+
+/// As with RA options, only parsed into a typed variant if it round trips.
+pub enum Dhcpv6Option {
+    /// Left unknown if the encapsulated message can't be parsed
+    RelayMessage(Box<Dhcpv6Message>),
+    /// IA_NA, IA_TA, IA_PD, and IA address/prefix options, which have fixed fields
+    /// followed by more options. Left unknown if the encapsulated options can't
+    /// be parsed.
+    Encapsulating {
+        code: u16,
+        /// The fixed fields before the encapsulated options
+        header: Vec<u8>,
+        options: Vec<Dhcpv6Option>,
+    },
+    DnsServers(Vec<Ipv6Addr>),
+    /// Concatenated names in dns wire format. Only generated, upstream domain lists
+    /// are parsed as unknown.
+    DomainList(Vec<u8>),
+    /// Captive portal API URI. Only generated, upstream captive portal options are
+    /// parsed as unknown.
+    CaptivePortal(String),
+    /// Seconds. Only generated.
+    InformationRefreshTime(u32),
+    /// Server addresses. Only generated, upstream NTP options are parsed as unknown.
+    NtpServers(Vec<Ipv6Addr>),
+    Unknown {
+        code: u16,
+        data: Vec<u8>,
+    },
+}
+
+impl Dhcpv6Message {
+    pub fn parse(source: &[u8]) -> Option<Self> {
+        return Dhcpv6Message::parse_nested(source, 0);
+    }
+
+    fn parse_nested(source: &[u8], depth: usize) -> Option<Self> {
+        let msg_type = *source.first()?;
+        let header;
+        let options_start;
+        match msg_type {
+            MSG_RELAY_FORW | MSG_RELAY_REPL => {
+                header = Dhcpv6Header::Relay {
+                    hop_count: *source.get(1)?,
+                    link_address: read_ipv6(source, 2)?,
+                    peer_address: read_ipv6(source, 18)?,
+                };
+                options_start = DHCPV6_RELAY_FIXED_HEADER_SIZE;
+            },
+            _ => {
+                header = Dhcpv6Header::Client { transaction_id: source.get(1 .. 4)?.try_into().unwrap() };
+                options_start = DHCPV6_FIXED_HEADER_SIZE;
+            },
+        }
+        return Some(Dhcpv6Message {
+            msg_type: msg_type,
+            header: header,
+            options: Dhcpv6Option::parse_all(source.get(options_start..)?, depth)?,
+        });
+    }
+
+    pub fn serialize(&self, out: &mut Vec<u8>) {
+        out.push(self.msg_type);
+        match &self.header {
+            Dhcpv6Header::Client { transaction_id } => {
+                out.extend_from_slice(transaction_id);
+            },
+            Dhcpv6Header::Relay { hop_count, link_address, peer_address } => {
+                out.push(*hop_count);
+                out.extend_from_slice(&link_address.octets());
+                out.extend_from_slice(&peer_address.octets());
+            },
+        }
+        for option in &self.options {
+            option.serialize(out);
+        }
+    }
+}
+
+impl Dhcpv6Option {
+    fn parse_all(source: &[u8], depth: usize) -> Option<Vec<Self>> {
+        let mut options = vec![];
+        let mut at_option_start = 0;
+        while at_option_start < source.len() {
+            let code = read_u16(source, at_option_start)?;
+            let data_start = at_option_start + 4;
+            let data_end = data_start + read_u16(source, at_option_start + 2)? as usize;
+            options.push(Dhcpv6Option::parse(code, source.get(data_start .. data_end)?, depth));
+            at_option_start = data_end;
+        }
+        return Some(options);
+    }
+
+    fn parse(code: u16, data: &[u8], depth: usize) -> Self {
+        match code {
+            OPT_RELAY_MSG if depth < MAX_NESTING => {
+                if let Some(message) = Dhcpv6Message::parse_nested(data, depth + 1) {
+                    return Dhcpv6Option::RelayMessage(Box::new(message));
+                }
+            },
+            // Vendor options (17) aren't included, their sub-option codes are specific to the
+            // enterprise number
+            //
+            // * https://datatracker.ietf.org/doc/html/rfc8415#section-21.17
+            OPT_IA_NA | OPT_IA_TA | OPT_IAADDR | OPT_IA_PD | OPT_IAPREFIX if depth < MAX_NESTING => {
+                let header_size = match code {
+                    OPT_IA_NA | OPT_IA_PD => 12,
+                    OPT_IA_TA => 4,
+                    OPT_IAADDR => 24,
+                    OPT_IAPREFIX => 25,
+                    _ => unreachable!(),
+                };
+                if let Some(header) = data.get(.. header_size) {
+                    if let Some(options) = Dhcpv6Option::parse_all(&data[header_size..], depth + 1) {
+                        return Dhcpv6Option::Encapsulating {
+                            code: code,
+                            header: header.to_vec(),
+                            options: options,
+                        };
+                    }
+                }
+            },
+            OPT_DNS_SERVERS if data.len() % 16 == 0 => {
+                return Dhcpv6Option::DnsServers(data.chunks_exact(16).map(|a| read_ipv6(a, 0).unwrap()).collect());
+            },
+            _ => { },
+        }
+        return Dhcpv6Option::Unknown {
+            code: code,
+            data: data.to_vec(),
+        };
+    }
+
+    pub fn code(&self) -> u16 {
+        match self {
+            Dhcpv6Option::RelayMessage(_) => return OPT_RELAY_MSG,
+            Dhcpv6Option::Encapsulating { code, .. } => return *code,
+            Dhcpv6Option::DnsServers(_) => return OPT_DNS_SERVERS,
+            Dhcpv6Option::DomainList(_) => return OPT_DOMAIN_LIST,
+            Dhcpv6Option::CaptivePortal(_) => return OPT_CAPTIVE_PORTAL,
+            Dhcpv6Option::InformationRefreshTime(_) => return OPT_INFORMATION_REFRESH_TIME,
+            Dhcpv6Option::NtpServers(_) => return OPT_NTP_SERVER,
+            Dhcpv6Option::Unknown { code, .. } => return *code,
+        }
+    }
+
+    fn serialize(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.code().to_be_bytes());
+
+        // Length, filled in after data
+        let length_start = out.len();
+        out.extend_from_slice(&[0, 0]);
+        match self {
+            Dhcpv6Option::RelayMessage(message) => {
+                message.serialize(out);
+            },
+            Dhcpv6Option::Encapsulating { header, options, .. } => {
+                out.extend_from_slice(header);
+                for option in options {
+                    option.serialize(out);
+                }
+            },
+            Dhcpv6Option::DnsServers(addresses) => {
+                for address in addresses {
+                    out.extend_from_slice(&address.octets());
+                }
+            },
+            Dhcpv6Option::DomainList(names) => {
+                out.extend_from_slice(names);
+            },
+            Dhcpv6Option::CaptivePortal(uri) => {
+                out.extend_from_slice(uri.as_bytes());
+            },
+            Dhcpv6Option::InformationRefreshTime(seconds) => {
+                out.extend_from_slice(&seconds.to_be_bytes());
+            },
+            Dhcpv6Option::NtpServers(addresses) => {
+                for address in addresses {
+                    out.extend_from_slice(&NTP_SUBOPTION_SRV_ADDR.to_be_bytes());
+                    out.extend_from_slice(&16u16.to_be_bytes());
+                    out.extend_from_slice(&address.octets());
+                }
+            },
+            Dhcpv6Option::Unknown { data, .. } => {
+                out.extend_from_slice(data);
+            },
+        }
+        let length = (out.len() - length_start - 2) as u16;
+        out[length_start .. length_start + 2].copy_from_slice(&length.to_be_bytes());
+    }
+}
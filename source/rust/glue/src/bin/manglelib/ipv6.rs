@@ -0,0 +1,275 @@
+use {
+    super::{
+        dhcpv6::Dhcpv6Message,
+        icmpv6_udp_checksum,
+        ra::RouterAdvertisement,
+        read_ipv6,
+        read_u16,
+    },
+    std::net::Ipv6Addr,
+};
+
+// * https://datatracker.ietf.org/doc/html/rfc8200#section-3
+pub const IPV6_FIXED_HEADER_SIZE: usize = 40;
+pub const NEXT_HEADER_UDP: u8 = 17;
+pub const NEXT_HEADER_ICMPV6: u8 = 58;
+// * https://datatracker.ietf.org/doc/html/rfc4291#section-2.7.1
+pub const IPV6_ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+// * https://datatracker.ietf.org/doc/html/rfc8200#section-4
+pub const NEXT_HEADER_HOP_BY_HOP: u8 = 0;
+pub const NEXT_HEADER_ROUTING: u8 = 43;
+pub const NEXT_HEADER_FRAGMENT: u8 = 44;
+pub const NEXT_HEADER_DESTINATION_OPTIONS: u8 = 60;
+const ICMPV6_FIXED_HEADER_SIZE: usize = 4;
+pub const ICMPV6_TYPE_RA: u8 = 134;
+const UDP_FIXED_HEADER_SIZE: usize = 8;
+pub const UDP_PORT_DHCPV6_CLIENT: u16 = 546;
+pub const UDP_PORT_DHCPV6_SERVER: u16 = 547;
+
+pub struct Ipv6Packet {
+    /// Version, traffic class, flow label
+    pub version_class_flow: [u8; 4],
+    pub hop_limit: u8,
+    pub source: Ipv6Addr,
+    pub destination: Ipv6Addr,
+    pub extension_headers: Vec<ExtensionHeader>,
+    pub payload: Ipv6Payload,
+}
+
+/// Hop-by-hop, routing, or destination options header, passed through as is.
+/// Fragments (and anything else) aren't supported since the upper layer header may
+/// not be present or may be encrypted.
+pub struct ExtensionHeader {
+    pub type_: u8,
+    /// Everything after the next header byte, starting with the length
+    pub body: Vec<u8>,
+}
+
+/// The extension header chain walked from a raw packet, with details needed for
+/// the upper layer checksum.
+pub struct HeaderChain<'a> {
+    pub extension_headers: Vec<ExtensionHeader>,
+    pub next_header: u8,
+    pub upper: &'a [u8],
+    pub final_destination: Ipv6Addr,
+}
+
+pub enum Ipv6Payload {
+    Icmpv6(Icmpv6Message),
+    Udp(UdpDatagram),
+}
+
+// * https://datatracker.ietf.org/doc/html/rfc4443#section-2.1
+pub struct Icmpv6Message {
+    pub code: u8,
+    pub body: Icmpv6Body,
+}
+
+pub enum Icmpv6Body {
+    RouterAdvertisement(RouterAdvertisement),
+    Other {
+        type_: u8,
+        body: Vec<u8>,
+    },
+}
+
+// * https://datatracker.ietf.org/doc/html/rfc768
+pub struct UdpDatagram {
+    pub source_port: u16,
+    pub destination_port: u16,
+    pub payload: UdpPayload,
+}
+
+pub enum UdpPayload {
+    Dhcpv6(Dhcpv6Message),
+    Other(Vec<u8>),
+}
+
+impl<'a> HeaderChain<'a> {
+    pub fn parse(source: &'a [u8]) -> Option<Self> {
+        let payload_length = read_u16(source, 4)? as usize;
+        let payload_end = IPV6_FIXED_HEADER_SIZE + payload_length;
+        let mut next_header = *source.get(6)?;
+        let mut final_destination = read_ipv6(source, 24)?;
+        let mut extension_headers = vec![];
+        let mut at_header_start = IPV6_FIXED_HEADER_SIZE;
+        while matches!(next_header, NEXT_HEADER_HOP_BY_HOP | NEXT_HEADER_ROUTING | NEXT_HEADER_DESTINATION_OPTIONS) {
+            let header_length = (*source.get(at_header_start + 1)? as usize + 1) * 8;
+            if at_header_start + header_length > payload_end {
+                return None;
+            }
+            let header = source.get(at_header_start .. at_header_start + header_length)?;
+            if next_header == NEXT_HEADER_ROUTING {
+                // * https://datatracker.ietf.org/doc/html/rfc8200#section-4.4
+                //
+                // If there are segments left, the checksum is computed against the last
+                // destination.
+                let routing_type = header[2];
+                let segments_left = header[3];
+                if segments_left > 0 {
+                    final_destination = match routing_type {
+                        // Type 0 (deprecated), type 2 (mobile ipv6): ordered list of addresses
+                        0 | 2 => read_ipv6(header, header_length.checked_sub(16)?)?,
+                        // Type 4 (segment routing): list in reverse order
+                        //
+                        // * https://datatracker.ietf.org/doc/html/rfc8754#section-2
+                        4 => read_ipv6(header, 8)?,
+                        // Must be discarded per rfc
+                        _ => return None,
+                    };
+                }
+            }
+            extension_headers.push(ExtensionHeader {
+                type_: next_header,
+                body: header[1..].to_vec(),
+            });
+            next_header = header[0];
+            at_header_start += header_length;
+        }
+        return Some(HeaderChain {
+            extension_headers: extension_headers,
+            next_header: next_header,
+            upper: source.get(at_header_start .. payload_end)?,
+            final_destination: final_destination,
+        });
+    }
+}
+
+impl Ipv6Packet {
+    pub fn parse(source: &[u8]) -> Option<Self> {
+        let chain = HeaderChain::parse(source)?;
+        return Some(Ipv6Packet {
+            version_class_flow: source.get(0 .. 4)?.try_into().unwrap(),
+            hop_limit: *source.get(7)?,
+            source: read_ipv6(source, 8)?,
+            destination: read_ipv6(source, 24)?,
+            extension_headers: chain.extension_headers,
+            payload: match chain.next_header {
+                NEXT_HEADER_ICMPV6 => Ipv6Payload::Icmpv6(Icmpv6Message::parse(chain.upper)?),
+                NEXT_HEADER_UDP => Ipv6Payload::Udp(UdpDatagram::parse(chain.upper)?),
+                _ => return None,
+            },
+        });
+    }
+
+    /// Returns None if the payload is too large for the length field (jumbograms
+    /// aren't supported).
+    pub fn serialize(&self) -> Option<Vec<u8>> {
+        let mut upper = vec![];
+        let upper_next_header;
+        let checksum_offset;
+        match &self.payload {
+            Ipv6Payload::Icmpv6(m) => {
+                upper_next_header = NEXT_HEADER_ICMPV6;
+                checksum_offset = 2;
+                m.serialize(&mut upper);
+            },
+            Ipv6Payload::Udp(d) => {
+                upper_next_header = NEXT_HEADER_UDP;
+                checksum_offset = 6;
+                d.serialize(&mut upper);
+            },
+        }
+        let mut extension_headers = vec![];
+        for (i, header) in self.extension_headers.iter().enumerate() {
+            extension_headers.push(match self.extension_headers.get(i + 1) {
+                Some(next) => next.type_,
+                None => upper_next_header,
+            });
+            extension_headers.extend_from_slice(&header.body);
+        }
+        let payload_length = u16::try_from(extension_headers.len() + upper.len()).ok()?;
+        let mut out = Vec::with_capacity(IPV6_FIXED_HEADER_SIZE + payload_length as usize);
+        out.extend_from_slice(&self.version_class_flow);
+        out.extend_from_slice(&payload_length.to_be_bytes());
+        out.push(match self.extension_headers.first() {
+            Some(first) => first.type_,
+            None => upper_next_header,
+        });
+        out.push(self.hop_limit);
+        out.extend_from_slice(&self.source.octets());
+        out.extend_from_slice(&self.destination.octets());
+        out.extend_from_slice(&extension_headers);
+        out.extend_from_slice(&upper);
+
+        // Checksum field is zero from serializing, sum then fill in
+        let mut checksum = icmpv6_udp_checksum(&out).unwrap();
+        if upper_next_header == NEXT_HEADER_UDP && checksum == [0, 0] {
+            // Zero means "no checksum" for UDP, send the equivalent all-ones instead
+            checksum = [0xff, 0xff];
+        }
+        let checksum_start = IPV6_FIXED_HEADER_SIZE + extension_headers.len() + checksum_offset;
+        out[checksum_start .. checksum_start + 2].copy_from_slice(&checksum);
+        return Some(out);
+    }
+}
+
+impl Icmpv6Message {
+    fn parse(source: &[u8]) -> Option<Self> {
+        let type_ = *source.first()?;
+        let code = *source.get(1)?;
+        let body = source.get(ICMPV6_FIXED_HEADER_SIZE..)?;
+        return Some(Icmpv6Message {
+            code: code,
+            body: match type_ {
+                ICMPV6_TYPE_RA => Icmpv6Body::RouterAdvertisement(RouterAdvertisement::parse(body)?),
+                _ => Icmpv6Body::Other {
+                    type_: type_,
+                    body: body.to_vec(),
+                },
+            },
+        });
+    }
+
+    fn serialize(&self, out: &mut Vec<u8>) {
+        out.push(match &self.body {
+            Icmpv6Body::RouterAdvertisement(_) => ICMPV6_TYPE_RA,
+            Icmpv6Body::Other { type_, .. } => *type_,
+        });
+        out.push(self.code);
+
+        // Checksum, filled in by ipv6 layer
+        out.extend_from_slice(&[0, 0]);
+        match &self.body {
+            Icmpv6Body::RouterAdvertisement(ra) => ra.serialize(out),
+            Icmpv6Body::Other { body, .. } => out.extend_from_slice(body),
+        }
+    }
+}
+
+impl UdpDatagram {
+    fn parse(source: &[u8]) -> Option<Self> {
+        let source_port = read_u16(source, 0)?;
+        let destination_port = read_u16(source, 2)?;
+        let length = read_u16(source, 4)? as usize;
+        let payload = source.get(UDP_FIXED_HEADER_SIZE .. length)?;
+        let is_dhcpv6 =
+            [source_port, destination_port]
+                .iter()
+                .any(|p| *p == UDP_PORT_DHCPV6_CLIENT || *p == UDP_PORT_DHCPV6_SERVER);
+        return Some(UdpDatagram {
+            source_port: source_port,
+            destination_port: destination_port,
+            payload: if is_dhcpv6 {
+                UdpPayload::Dhcpv6(Dhcpv6Message::parse(payload)?)
+            } else {
+                UdpPayload::Other(payload.to_vec())
+            },
+        });
+    }
+
+    fn serialize(&self, out: &mut Vec<u8>) {
+        let start = out.len();
+        out.extend_from_slice(&self.source_port.to_be_bytes());
+        out.extend_from_slice(&self.destination_port.to_be_bytes());
+
+        // Length, checksum - filled in below and by ipv6 layer
+        out.extend_from_slice(&[0, 0, 0, 0]);
+        match &self.payload {
+            UdpPayload::Dhcpv6(m) => m.serialize(out),
+            UdpPayload::Other(p) => out.extend_from_slice(p),
+        }
+        let length = (out.len() - start) as u16;
+        out[start + 4 .. start + 6].copy_from_slice(&length.to_be_bytes());
+    }
+}
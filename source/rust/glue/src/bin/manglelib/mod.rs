@@ -1,16 +1,116 @@
 use {
-    flowcontrol::shed,
+    dhcpv6::{
+        Dhcpv6Message,
+        Dhcpv6Option,
+        MSG_ADVERTISE,
+        MSG_RELAY_REPL,
+        MSG_REPLY,
+        OPT_DNS_SERVERS,
+        OPT_DOMAIN_LIST,
+    },
+    ipv6::{
+        HeaderChain,
+        Icmpv6Body,
+        Ipv6Packet,
+        Ipv6Payload,
+        UdpPayload,
+        ICMPV6_TYPE_RA,
+        IPV6_ALL_NODES,
+        IPV6_FIXED_HEADER_SIZE,
+        NEXT_HEADER_DESTINATION_OPTIONS,
+        NEXT_HEADER_FRAGMENT,
+        NEXT_HEADER_HOP_BY_HOP,
+        NEXT_HEADER_ICMPV6,
+        NEXT_HEADER_ROUTING,
+        NEXT_HEADER_UDP,
+        UDP_PORT_DHCPV6_SERVER,
+    },
+    ra::{
+        pref64_option,
+        route_information_option,
+        RaOption,
+        CAPTIVE_PORTAL_MAX_URI_SIZE,
+        DNSSL_MAX_NAMES_SIZE,
+        OPT_CAPTIVE_PORTAL,
+        OPT_DNSSL,
+        OPT_MTU,
+        OPT_PREF64,
+        OPT_PREFIX_INFORMATION,
+        OPT_RDNSS,
+        OPT_ROUTE_INFORMATION,
+        PIO_FLAG_AUTONOMOUS,
+        PIO_FLAG_ON_LINK,
+        RA_FLAG_MANAGED,
+        RA_FLAG_OTHER,
+        RDNSS_MAX_ADDRESSES,
+        RouterAdvertisement,
+        RouterPreference,
+    },
     std::net::Ipv6Addr,
 };
 
+pub mod ipv6;
+pub mod ra;
+pub mod dhcpv6;
+
 #[cfg(test)]
 mod test_modify_dhcp_ex1;
 #[cfg(test)]
+mod test_modify_dhcp_nested;
+#[cfg(test)]
 mod test_checksum;
 #[cfg(test)]
 mod test_ra_modify_mtu;
 #[cfg(test)]
 mod test_ra_inject_mtu;
+#[cfg(test)]
+mod test_roundtrip;
+#[cfg(test)]
+mod test_extension_headers;
+#[cfg(test)]
+mod test_ra_pref64;
+#[cfg(test)]
+mod test_search_domains;
+#[cfg(test)]
+mod test_inject_dns;
+#[cfg(test)]
+mod test_dhcp_message_types;
+#[cfg(test)]
+mod test_multiple_resolvers;
+#[cfg(test)]
+mod test_fuzz;
+#[cfg(test)]
+mod test_validation;
+#[cfg(test)]
+mod test_ra_pio;
+#[cfg(test)]
+mod test_ra_header;
+#[cfg(test)]
+mod test_ra_route_information;
+#[cfg(test)]
+mod test_captive_portal;
+#[cfg(test)]
+mod test_unsolicited_ra;
+
+/// Lifetime (seconds) of injected PREF64, route information and DNSSL options when
+/// not configured, 3 × the default MaxRtrAdvInterval. Not the upstream router
+/// lifetime, which is 0 for non-default routers and would withdraw the options.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc8781#section-4.1
+/// * https://datatracker.ietf.org/doc/html/rfc8106#section-5.2
+pub const INJECTED_OPTION_LIFETIME_DEFAULT: u32 = 1800;
+
+fn read_u16(source: &[u8], start: usize) -> Option<u16> {
+    return Some(u16::from_be_bytes(source.get(start .. start + 2)?.try_into().unwrap()));
+}
+
+fn read_u32(source: &[u8], start: usize) -> Option<u32> {
+    return Some(u32::from_be_bytes(source.get(start .. start + 4)?.try_into().unwrap()));
+}
+
+fn read_ipv6(source: &[u8], start: usize) -> Option<Ipv6Addr> {
+    return Some(Ipv6Addr::from(<[u8; 16]>::try_from(source.get(start .. start + 16)?).unwrap()));
+}
 
 #[inline]
 fn checksum_roll(sum32: &mut u32, bytes: &[u8]) {
@@ -24,14 +124,16 @@ fn checksum_roll(sum32: &mut u32, bytes: &[u8]) {
     }
 }
 
-fn checksum_finish(sum32: u32) -> [u8; 2] {
-    let high = (sum32 >> 16) as u16;
-    let low = (sum32 & 0xFFFF) as u16;
-    return (!(high + low).to_be()).to_be_bytes();
+fn checksum_finish(mut sum32: u32) -> [u8; 2] {
+    // Fold carries until it fits in 16 bits, a single fold can carry again
+    while sum32 > 0xFFFF {
+        sum32 = (sum32 >> 16) + (sum32 & 0xFFFF);
+    }
+    return (!(sum32 as u16).to_be()).to_be_bytes();
 }
 
 fn icmpv6_udp_checksum(source: &[u8]) -> Option<[u8; 2]> {
-    // * IPv6 pseudo-header https://datatracker.ietf.org/doc/html/rfc2460#section-8.1
+    // * IPv6 pseudo-header https://datatracker.ietf.org/doc/html/rfc8200#section-8.1
     //
     // * ICMP https://datatracker.ietf.org/doc/html/rfc4443#section-2.3
     //
@@ -40,222 +142,700 @@ fn icmpv6_udp_checksum(source: &[u8]) -> Option<[u8; 2]> {
     // * UDP https://datatracker.ietf.org/doc/html/rfc768
     //
     //   Pseudo header + whole body
+    //
+    // The pseudo header uses the upper layer header and length (after any extension
+    // headers) and the final destination if there's a routing header.
+    let chain = HeaderChain::parse(source)?;
     let mut sum32 = 0u32;
 
-    // Icmpv6 length (pseudo header)
-    checksum_roll(&mut sum32, source.get(4 .. 6)?);
+    // Source addr, dest addr (pseudo header)
+    checksum_roll(&mut sum32, source.get(8 .. 24)?);
+    checksum_roll(&mut sum32, &chain.final_destination.octets());
+
+    // Upper layer length (pseudo header)
+    checksum_roll(&mut sum32, &(chain.upper.len() as u32).to_be_bytes());
 
     // Next header (pseudo header)
-    sum32 += u16::from_ne_bytes([0x00, *source.get(6)?]) as u32;
+    sum32 += u16::from_ne_bytes([0x00, chain.next_header]) as u32;
 
-    // Source addr (pseudo header), dest addr (pseudo header), payload
-    checksum_roll(&mut sum32, source.get(8..)?);
+    // Payload
+    checksum_roll(&mut sum32, chain.upper);
 
     // Then do some rfc magic
     return Some(checksum_finish(sum32));
 }
 
-pub fn modify(source: &[u8], ip: Ipv6Addr, mtu: Option<u32>) -> Option<Vec<u8>> {
-    let mut ipv6_packet = vec![];
-    ipv6_packet.reserve(source.len() + 128);
-    ipv6_packet.extend_from_slice(source);
-
-    #[must_use]
-    fn splice(packet: &mut Vec<u8>, start: usize, end: Option<usize>, data: &[u8]) -> Option<()> {
-        if start > packet.len() {
+/// Encodes names in dns wire format (uncompressed labels, each name terminated by
+/// the empty label) for DNSSL and the DHCPv6 domain list. Returns None if any name
+/// is invalid.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc1035#section-3.1
+pub fn encode_dns_names(names: &[String]) -> Option<Vec<u8>> {
+    let mut out = vec![];
+    for name in names {
+        let start = out.len();
+        for label in name.strip_suffix(".").unwrap_or(name).split(".") {
+            if label.is_empty() || label.len() > 63 {
+                return None;
+            }
+            out.push(label.len() as u8);
+            out.extend_from_slice(label.as_bytes());
+        }
+        out.push(0);
+        if out.len() - start > 255 {
             return None;
         }
-        if let Some(end) = end {
-            if end > packet.len() {
-                return None;
+    }
+    return Some(out);
+}
+
+pub struct Pref64Config {
+    pub prefix: Ipv6Addr,
+    /// One of 96, 64, 56, 48, 40, 32
+    pub prefix_length: u8,
+    /// Seconds. Defaults to `INJECTED_OPTION_LIFETIME_DEFAULT`.
+    pub lifetime: Option<u32>,
+}
+
+/// A more specific route via the RA's source router, like the NAT64 prefix.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc4191#section-2.3
+pub struct RouteConfig {
+    pub prefix: Ipv6Addr,
+    pub prefix_length: u8,
+    pub preference: RouterPreference,
+    /// Seconds. Defaults to `INJECTED_OPTION_LIFETIME_DEFAULT`.
+    pub lifetime: Option<u32>,
+}
+
+pub struct SearchDomainsConfig {
+    pub domains: Vec<String>,
+    /// Seconds, for the RA DNSSL. Defaults to `INJECTED_OPTION_LIFETIME_DEFAULT`.
+    pub lifetime: Option<u32>,
+}
+
+/// Rewriting of upstream RA prefix information options.
+#[derive(Default, PartialEq, Eq)]
+pub struct PioConfig {
+    /// Seconds. Lifetimes of 0 (withdrawing the prefix) aren't raised.
+    pub min_valid_lifetime: Option<u32>,
+    /// Seconds
+    pub max_valid_lifetime: Option<u32>,
+    /// Seconds. Lifetimes of 0 (deprecating the prefix) aren't raised.
+    pub min_preferred_lifetime: Option<u32>,
+    /// Seconds
+    pub max_preferred_lifetime: Option<u32>,
+    /// Force the on-link (L) flag set or cleared
+    pub on_link: Option<bool>,
+    /// Force the autonomous address configuration (A) flag set or cleared
+    pub autonomous: Option<bool>,
+    /// Drop options for prefixes within any of these (prefix, prefix length)
+    pub drop_prefixes: Vec<(Ipv6Addr, u8)>,
+}
+
+/// Overrides for upstream RA header fields.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc4861#section-4.2
+#[derive(Default, PartialEq, Eq)]
+pub struct RaHeaderConfig {
+    /// Force the managed address configuration (M) flag set or cleared
+    pub managed: Option<bool>,
+    /// Force the other configuration (O) flag set or cleared. Setting it makes
+    /// clients ask DHCPv6 for DNS servers even if addresses come from SLAAC.
+    pub other: Option<bool>,
+    /// Seconds. 0 means the router isn't a default router.
+    pub router_lifetime: Option<u16>,
+    /// Ignored (cleared) when the router lifetime ends up 0, per RFC 4191
+    pub preference: Option<RouterPreference>,
+    pub cur_hop_limit: Option<u8>,
+    /// Milliseconds
+    pub reachable_time: Option<u32>,
+    /// Milliseconds
+    pub retrans_timer: Option<u32>,
+}
+
+#[derive(Default)]
+pub struct ModifyConfig {
+    /// Override/inject RA MTU
+    pub mtu: Option<u32>,
+    /// Override/inject RA NAT64 prefix
+    pub pref64: Option<Pref64Config>,
+    /// Inject RA route information, replacing upstream route information for the
+    /// same prefixes
+    pub routes: Vec<RouteConfig>,
+    /// Rewrite RA prefix information
+    pub pio: Option<PioConfig>,
+    /// Override RA header fields
+    pub ra_header: Option<RaHeaderConfig>,
+    /// Override/inject RA DNSSL and DHCPv6 domain list
+    pub search_domains: Option<SearchDomainsConfig>,
+    /// Override/inject the RA and DHCPv6 captive portal API URI
+    ///
+    /// * https://datatracker.ietf.org/doc/html/rfc8908
+    pub captive_portal: Option<String>,
+    /// Inject RDNSS/DHCPv6 DNS servers even if the upstream RA/reply has none, using
+    /// this RDNSS lifetime (seconds) when there's no upstream lifetime to copy.
+    pub inject_dns_lifetime: Option<u32>,
+}
+
+/// Why a packet wasn't modified.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ModifyError {
+    /// No resolver addresses to advertise
+    NoIp,
+    /// Malformed, or headers or a protocol that isn't supported
+    Parse,
+    /// Bad checksum, or an RA that fails RFC 4861 validation
+    Invalid,
+    /// ICMPv6 but not an RA
+    NotRa,
+    /// UDP but not DHCPv6
+    NotDhcpv6,
+    /// Search domains or PREF64 couldn't be encoded, or generated options don't fit
+    /// in the packet
+    Config,
+}
+
+/// Which options were generated while modifying a packet.
+#[derive(Default, Clone, PartialEq, Eq, Debug)]
+pub struct ModifyReport {
+    pub mtu: bool,
+    pub rdnss: bool,
+    pub pref64: bool,
+    pub route_information: bool,
+    pub dnssl: bool,
+    pub captive_portal: bool,
+    pub dhcpv6_dns_servers: bool,
+    pub dhcpv6_domain_list: bool,
+    pub dhcpv6_captive_portal: bool,
+    /// Only collected if set to `Some` before calling `modify`.
+    pub trace: Option<PacketTrace>,
+}
+
+/// Details of how a packet was modified, for debugging. Options are RA option types
+/// or DHCPv6 option codes, including options in relayed messages.
+#[derive(Default, Clone, PartialEq, Eq, Debug)]
+pub struct PacketTrace {
+    /// Like `RA` or `DHCPv6 7`, empty if the packet couldn't be parsed
+    pub message: String,
+    pub options_seen: Vec<u16>,
+    pub options_removed: Vec<u16>,
+    pub options_added: Vec<u16>,
+}
+
+fn clamp_lifetime(lifetime: u32, min: Option<u32>, max: Option<u32>) -> u32 {
+    let mut out = lifetime;
+    if let Some(max) = max {
+        out = out.min(max);
+    }
+    if let (Some(min), true) = (min, out != 0) {
+        out = out.max(min);
+    }
+    return out;
+}
+
+fn set_flag(flags: &mut u8, flag: u8, set: Option<bool>) {
+    match set {
+        Some(true) => *flags |= flag,
+        Some(false) => *flags &= !flag,
+        None => { },
+    }
+}
+
+fn prefix_within(prefix: Ipv6Addr, prefix_length: u8, outer: Ipv6Addr, outer_length: u8) -> bool {
+    if prefix_length < outer_length || outer_length > 128 {
+        return false;
+    }
+    let mask = (!0u128).checked_shl(128 - outer_length as u32).unwrap_or(0);
+    return u128::from(prefix) & mask == u128::from(outer) & mask;
+}
+
+/// Replaces DNS options within encapsulating options (IAs), at any depth. Returns
+/// whether anything was replaced.
+fn modify_dhcpv6_nested_dns(options: &mut [Dhcpv6Option], ips: &[Ipv6Addr]) -> bool {
+    let mut modified = false;
+    for option in options {
+        if let Dhcpv6Option::Encapsulating { options: encapsulated, .. } = option {
+            for encapsulated_option in encapsulated.iter_mut() {
+                if encapsulated_option.code() == OPT_DNS_SERVERS {
+                    *encapsulated_option = Dhcpv6Option::DnsServers(ips.to_vec());
+                    modified = true;
+                }
+            }
+            modified |= modify_dhcpv6_nested_dns(encapsulated, ips);
+        }
+    }
+    return modified;
+}
+
+/// Returns whether the message was modified. Advertise and Reply (including replies
+/// to Information-Request) messages are modified, Relay-Reply messages are modified
+/// by modifying the relayed message, anything else is passed through.
+fn modify_dhcpv6(
+    message: &mut Dhcpv6Message,
+    ips: &[Ipv6Addr],
+    config: &ModifyConfig,
+    report: &mut ModifyReport,
+) -> Result<bool, ModifyError> {
+    if let Some(trace) = &mut report.trace {
+        if trace.message.is_empty() {
+            trace.message = format!("DHCPv6 {}", message.msg_type);
+        }
+        trace.options_seen.extend(message.options.iter().map(|o| o.code()));
+    }
+    match message.msg_type {
+        MSG_ADVERTISE | MSG_REPLY => {
+            // Replace DNS nested in other options in place
+            let mut modified = modify_dhcpv6_nested_dns(&mut message.options, ips);
+            report.dhcpv6_dns_servers |= modified;
+            if let (true, Some(trace)) = (modified, &mut report.trace) {
+                trace.options_removed.push(OPT_DNS_SERVERS);
+                trace.options_added.push(OPT_DNS_SERVERS);
+            }
+
+            // Drop existing DNS, domain list if replacing
+            let mut found_dns = false;
+            let mut removed = vec![];
+            message.options.retain(|option| {
+                let keep = match option.code() {
+                    OPT_DNS_SERVERS => {
+                        found_dns = true;
+                        false
+                    },
+                    OPT_DOMAIN_LIST => config.search_domains.is_none(),
+                    dhcpv6::OPT_CAPTIVE_PORTAL => config.captive_portal.is_none(),
+                    _ => true,
+                };
+                if !keep {
+                    removed.push(option.code());
+                }
+                return keep;
+            });
+            let kept = message.options.len();
+            let inject_dns = found_dns || config.inject_dns_lifetime.is_some();
+
+            // Generate custom DNS option
+            if inject_dns {
+                message.options.push(Dhcpv6Option::DnsServers(ips.to_vec()));
+                report.dhcpv6_dns_servers = true;
+                modified = true;
+            }
+
+            // Generate custom domain list
+            if let Some(search_domains) = &config.search_domains {
+                let names = encode_dns_names(&search_domains.domains).ok_or(ModifyError::Config)?;
+                message.options.push(Dhcpv6Option::DomainList(names));
+                report.dhcpv6_domain_list = true;
+                modified = true;
+            }
+
+            // Generate custom captive portal
+            if let Some(uri) = &config.captive_portal {
+                message.options.push(Dhcpv6Option::CaptivePortal(uri.clone()));
+                report.dhcpv6_captive_portal = true;
+                modified = true;
+            }
+            if let Some(trace) = &mut report.trace {
+                trace.options_removed.extend(removed);
+                trace.options_added.extend(message.options[kept..].iter().map(|o| o.code()));
+            }
+            return Ok(modified);
+        },
+        // * https://datatracker.ietf.org/doc/html/rfc8415#section-19.3
+        MSG_RELAY_REPL => {
+            let mut modified = false;
+            for option in &mut message.options {
+                if let Dhcpv6Option::RelayMessage(relayed) = option {
+                    modified |= modify_dhcpv6(relayed, ips, config, report)?;
+                }
+            }
+            return Ok(modified);
+        },
+        _ => {
+            return Ok(false);
+        },
+    }
+}
+
+/// Checks the upper layer checksum and RFC 4861 RA validity, so corrupt or spoofed
+/// packets aren't rewritten with a fresh checksum. Zero length RA options are
+/// rejected when parsing.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc4861#section-6.1.2
+fn validate(source: &[u8], packet: &Ipv6Packet) -> bool {
+    // Zero means no UDP checksum, which isn't allowed over IPv6
+    //
+    // * https://datatracker.ietf.org/doc/html/rfc8200#section-8.1
+    let Some(chain) = HeaderChain::parse(source) else {
+        return false;
+    };
+    if chain.next_header == NEXT_HEADER_UDP && chain.upper.get(6 .. 8) == Some(&[0, 0]) {
+        return false;
+    }
+
+    // Summing over the checksum field too gives zero if it's correct
+    if icmpv6_udp_checksum(source) != Some([0, 0]) {
+        return false;
+    }
+    if let Ipv6Payload::Icmpv6(icmpv6) = &packet.payload {
+        if let Icmpv6Body::RouterAdvertisement(_) = &icmpv6.body {
+            // Hop limit 255 means it wasn't forwarded by a router, routers only send RAs
+            // from link-local addresses. The ICMP length (at least 16) is checked when
+            // parsing.
+            if packet.hop_limit != 255 || packet.source.segments()[0] & 0xffc0 != 0xfe80 || icmpv6.code != 0 {
+                return false;
             }
-            packet.splice(start .. end, data.iter().cloned());
-        } else {
-            packet.splice(start.., data.iter().cloned());
         }
-        return Some(());
     }
+    return true;
+}
 
-    #[must_use]
-    fn replace_u16(packet: &mut Vec<u8>, start: usize, data: &[u8; 2]) -> Option<()> {
-        packet.get_mut(start .. start + 2)?.copy_from_slice(data);
-        return Some(());
+/// Whether the upper layer is an RA, without parsing or validating the RA itself.
+pub fn is_ra(source: &[u8]) -> bool {
+    let Some(chain) = HeaderChain::parse(source) else {
+        return false;
+    };
+    return chain.next_header == NEXT_HEADER_ICMPV6 && chain.upper.first() == Some(&ICMPV6_TYPE_RA);
+}
+
+/// The router lifetime (seconds) if the upper layer is an RA, without parsing or
+/// validating the rest of the RA.
+pub fn ra_router_lifetime(source: &[u8]) -> Option<u16> {
+    if !is_ra(source) {
+        return None;
     }
+    return read_u16(HeaderChain::parse(source)?.upper, 6);
+}
 
-    fn read_u16(packet: &[u8], start: usize) -> Option<u16> {
-        return Some(u16::from_be_bytes(packet.get(start .. start + 2)?.try_into().unwrap()));
+/// Whether the upper layer is a DHCPv6 Advertise, Reply or Relay-Reply from a
+/// server or relay, without parsing or validating the message itself.
+pub fn is_dhcpv6_reply(source: &[u8]) -> bool {
+    let Some(chain) = HeaderChain::parse(source) else {
+        return false;
+    };
+    if chain.next_header != NEXT_HEADER_UDP || read_u16(chain.upper, 0) != Some(UDP_PORT_DHCPV6_SERVER) {
+        return false;
     }
+    return matches!(chain.upper.get(8), Some(&(MSG_ADVERTISE | MSG_REPLY | MSG_RELAY_REPL)));
+}
 
-    const IPV6_PAYLOAD_START: usize = 40;
-    match *ipv6_packet.get(6)? {
+/// Whether a packet that failed parsing or validation could be an RA or come from
+/// the DHCPv6 server port. Headers are followed as far as the packet goes, ignoring
+/// the payload length, and anything that can't be ruled out (a truncated header, or
+/// a fragment without the upper layer header) counts.
+pub fn maybe_ra_or_dhcpv6_server(source: &[u8]) -> bool {
+    let Some(&first_next_header) = source.get(6) else {
+        return false;
+    };
+    let mut next_header = first_next_header;
+    let mut at = IPV6_FIXED_HEADER_SIZE;
+    loop {
+        match next_header {
+            NEXT_HEADER_HOP_BY_HOP | NEXT_HEADER_ROUTING | NEXT_HEADER_DESTINATION_OPTIONS => {
+                let Some(&[header_next_header, length]) = source.get(at .. at + 2) else {
+                    return true;
+                };
+                next_header = header_next_header;
+                at += (length as usize + 1) * 8;
+            },
+            // * https://datatracker.ietf.org/doc/html/rfc8200#section-4.5
+            NEXT_HEADER_FRAGMENT => {
+                let (Some(&header_next_header), Some(offset)) = (source.get(at), read_u16(source, at + 2)) else {
+                    return true;
+                };
+                if offset & !0x07 != 0 {
+                    return true;
+                }
+                next_header = header_next_header;
+                at += 8;
+            },
+            NEXT_HEADER_ICMPV6 => return source.get(at).is_none_or(|type_| *type_ == ICMPV6_TYPE_RA),
+            NEXT_HEADER_UDP => return read_u16(source, at).is_none_or(|port| port == UDP_PORT_DHCPV6_SERVER),
+            _ => return false,
+        }
+    }
+}
+
+/// The ICMPv6 message after any extension headers, if the upper layer is ICMPv6
+/// and the checksum is correct.
+pub fn checked_icmpv6(source: &[u8]) -> Option<&[u8]> {
+    let chain = HeaderChain::parse(source)?;
+    if chain.next_header != NEXT_HEADER_ICMPV6 {
+        return None;
+    }
+
+    // Summing over a correct checksum gives zero
+    if icmpv6_udp_checksum(source)? != [0, 0] {
+        return None;
+    }
+    return Some(chain.upper);
+}
+
+/// The UDP header and payload after any extension headers, if the upper layer is
+/// UDP and the checksum is correct. A zero checksum isn't allowed over IPv6.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc8200#section-8.1
+pub fn checked_udp(source: &[u8]) -> Option<&[u8]> {
+    let chain = HeaderChain::parse(source)?;
+    if chain.next_header != NEXT_HEADER_UDP || chain.upper.len() < 8 || read_u16(chain.upper, 6)? == 0 {
+        return None;
+    }
+    if icmpv6_udp_checksum(source)? != [0, 0] {
+        return None;
+    }
+    return Some(chain.upper);
+}
+
+/// `ips` are the resolver addresses to advertise, in order of preference. Generated
+/// options are recorded in `report`. Returns the packet unchanged if there was
+/// nothing to modify. Packets are parsed and validated before checking `ips`, so
+/// `NoIp` means the packet was otherwise fine.
+pub fn modify(
+    source: &[u8],
+    ips: &[Ipv6Addr],
+    config: &ModifyConfig,
+    report: &mut ModifyReport,
+) -> Result<Vec<u8>, ModifyError> {
+    let mut ipv6_packet = Ipv6Packet::parse(source).ok_or(ModifyError::Parse)?;
+    if !validate(source, &ipv6_packet) {
+        return Err(ModifyError::Invalid);
+    }
+    if ips.is_empty() {
+        return Err(ModifyError::NoIp);
+    }
+    match &mut ipv6_packet.payload {
         // ICMP
         //
         // * https://datatracker.ietf.org/doc/html/rfc4443
         //
-        // RA
-        //
-        // * https://datatracker.ietf.org/doc/html/rfc4861#section-4.2
-        //
-        // Only replace RA if present.  If it's not present, it may come via DHCP so don't
-        // add things here and confuse devices (also need the option for a lifetime to
-        // copy).
-        58 => {
+        // By default only replace RDNSS if present.  If it's not present, it may come via
+        // DHCP so don't add things here and confuse devices (also need the option for a
+        // lifetime to copy).
+        Ipv6Payload::Icmpv6(icmpv6) => {
             // Confirm it's RA
-            let Some(type_) = ipv6_packet.get(IPV6_PAYLOAD_START) else {
-                return None;
+            let Icmpv6Body::RouterAdvertisement(ra) = &mut icmpv6.body else {
+                return Err(ModifyError::NotRa);
             };
-            if *type_ != 134 {
-                return None;
-            }
 
-            // Modify RA
-            const OPT_MTU: u8 = 5;
-            const OPT_RDNSS: u8 = 25;
-            const RA_FIXED_HEADER_SIZE: usize = 16;
-            const RA_OPTIONS_START: usize = IPV6_PAYLOAD_START + RA_FIXED_HEADER_SIZE;
-
-            // Set other info flag
-            *ipv6_packet.get_mut(IPV6_PAYLOAD_START + 5)? |= 0x40;
+            if let Some(trace) = &mut report.trace {
+                trace.message = "RA".to_string();
+                trace.options_seen.extend(ra.options.iter().map(|o| o.type_() as u16));
+            }
 
-            // Copy options, find + filter out RDNSS
-            struct FoundRdnss {
-                lifetime: u16,
+            // Override header fields
+            let header_fields = |ra: &RouterAdvertisement| {
+                return (ra.cur_hop_limit, ra.flags, ra.router_lifetime, ra.reachable_time, ra.retrans_timer);
+            };
+            let upstream_header = header_fields(ra);
+            if let Some(header) = &config.ra_header {
+                set_flag(&mut ra.flags, RA_FLAG_MANAGED, header.managed);
+                set_flag(&mut ra.flags, RA_FLAG_OTHER, header.other);
+                if let Some(router_lifetime) = header.router_lifetime {
+                    ra.router_lifetime = router_lifetime;
+                }
+                if let Some(preference) = header.preference {
+                    ra.set_preference(preference);
+                }
+                if ra.router_lifetime == 0 && (header.router_lifetime.is_some() || header.preference.is_some()) {
+                    // * https://datatracker.ietf.org/doc/html/rfc4191#section-2.2
+                    ra.set_preference(RouterPreference::Medium);
+                }
+                if let Some(cur_hop_limit) = header.cur_hop_limit {
+                    ra.cur_hop_limit = cur_hop_limit;
+                }
+                if let Some(reachable_time) = header.reachable_time {
+                    ra.reachable_time = reachable_time;
+                }
+                if let Some(retrans_timer) = header.retrans_timer {
+                    ra.retrans_timer = retrans_timer;
+                }
             }
 
+            // Filter out RDNSS, MTU + PREF64 + DNSSL if replacing, and dropped prefixes
+            let mtu = config.mtu;
             let mut found_rdnss = None;
-            let mut at_option_start = RA_OPTIONS_START;
-            let mut new_options = vec![];
-            new_options.reserve(ipv6_packet.len() - IPV6_PAYLOAD_START);
-            let mut modify = false;
-            if mtu.is_some() {
-                modify = true;
-            }
-            loop {
-                if at_option_start == ipv6_packet.len() {
-                    break;
+            let mut removed = vec![];
+            ra.options.retain(|option| {
+                if let RaOption::Rdnss { lifetime, .. } = option {
+                    found_rdnss = Some(*lifetime);
                 }
-                let at_option_type = *ipv6_packet.get(at_option_start)?;
-                let at_option_length = *ipv6_packet.get(at_option_start + 1)? as usize * 8;
-                eprintln!("option type {} len {}; {:?}", at_option_type, at_option_length, mtu);
-                shed!{
-                    'next_option _;
-                    if at_option_type == OPT_RDNSS {
-                        found_rdnss = Some(FoundRdnss { lifetime: read_u16(&ipv6_packet, at_option_start + 4)? });
-                        modify = true;
-                        break 'next_option;
-                    }
-                    if mtu.is_some() && at_option_type == OPT_MTU {
-                        break 'next_option;
-                    }
-                    // Keep anything we're not going to modify
-                    new_options.extend_from_slice(ipv6_packet.get(at_option_start .. at_option_start + at_option_length)?);
+                let keep = match option.type_() {
+                    OPT_RDNSS => false,
+                    OPT_MTU => mtu.is_none(),
+                    OPT_PREF64 => config.pref64.is_none(),
+                    OPT_DNSSL => config.search_domains.is_none(),
+                    OPT_CAPTIVE_PORTAL => config.captive_portal.is_none(),
+                    OPT_ROUTE_INFORMATION => match option.route_prefix() {
+                        Some((prefix, prefix_length)) => {
+                            !config.routes.iter().any(|route| {
+                                route.prefix_length == prefix_length &&
+                                    prefix_within(prefix, prefix_length, route.prefix, route.prefix_length)
+                            })
+                        },
+                        None => true,
+                    },
+                    OPT_PREFIX_INFORMATION => match (option, &config.pio) {
+                        (RaOption::PrefixInformation { prefix, prefix_length, .. }, Some(pio)) => {
+                            !pio.drop_prefixes.iter().any(|(outer, outer_length)| {
+                                prefix_within(*prefix, *prefix_length, *outer, *outer_length)
+                            })
+                        },
+                        _ => true,
+                    },
+                    _ => true,
+                };
+                if !keep {
+                    removed.push(option.type_() as u16);
                 }
-                at_option_start += at_option_length;
+                return keep;
+            });
+            let kept = ra.options.len();
+            let rdnss_lifetime = found_rdnss.or(config.inject_dns_lifetime);
+            if mtu.is_none() && rdnss_lifetime.is_none() && config.pref64.is_none() &&
+                config.search_domains.is_none() &&
+                config.captive_portal.is_none() &&
+                config.pio.is_none() &&
+                config.routes.is_empty() &&
+                header_fields(ra) == upstream_header {
+                return Ok(source.to_vec());
             }
-            if !modify {
-                return Some(source.to_vec());
+
+            // Rewrite remaining prefix information
+            if let Some(pio) = &config.pio {
+                for option in &mut ra.options {
+                    let RaOption::PrefixInformation { flags, valid_lifetime, preferred_lifetime, .. } = option else {
+                        continue;
+                    };
+                    *valid_lifetime = clamp_lifetime(*valid_lifetime, pio.min_valid_lifetime, pio.max_valid_lifetime);
+                    *preferred_lifetime =
+                        clamp_lifetime(
+                            *preferred_lifetime,
+                            pio.min_preferred_lifetime,
+                            pio.max_preferred_lifetime,
+                        ).min(*valid_lifetime);
+                    set_flag(flags, PIO_FLAG_ON_LINK, pio.on_link);
+                    set_flag(flags, PIO_FLAG_AUTONOMOUS, pio.autonomous);
+                }
             }
 
             // Create custom MTU
             if let Some(mtu) = mtu {
-                new_options.push(OPT_MTU);
-                new_options.push(1u8);
-                new_options.extend_from_slice(&[0, 0]);
-                new_options.extend(mtu.to_be_bytes());
+                ra.options.push(RaOption::Mtu(mtu));
+                report.mtu = true;
             }
 
-            // Generate custom RDNSS
-            if let Some(found_rdnss) = found_rdnss {
-                new_options.push(OPT_RDNSS);
-                let lifetime_bytes = found_rdnss.lifetime.to_be_bytes();
-                let ip_bytes = ip.octets();
-                new_options.push(((1 + 1 + 2 + lifetime_bytes.len() + ip_bytes.len()) / 8) as u8);
-                new_options.extend_from_slice(&[0, 0]);
-                new_options.extend(lifetime_bytes);
-                new_options.extend(ip_bytes);
+            // Generate custom RDNSS, dropping the least preferred addresses if there are
+            // more than fit
+            if let Some(lifetime) = rdnss_lifetime {
+                ra.options.push(RaOption::Rdnss {
+                    lifetime: lifetime,
+                    addresses: ips[.. ips.len().min(RDNSS_MAX_ADDRESSES)].to_vec(),
+                });
+                report.rdnss = true;
             }
 
-            // Replace options
-            splice(&mut ipv6_packet, RA_OPTIONS_START, None, &new_options)?;
-
-            // Update ipv6 payload length
-            replace_u16(&mut ipv6_packet, 4, &((RA_FIXED_HEADER_SIZE + new_options.len()) as u16).to_be_bytes())?;
-
-            // Recalc checksum
-            ipv6_packet.get_mut(IPV6_PAYLOAD_START + 2 .. IPV6_PAYLOAD_START + 4)?.fill(0);
-            let new_checksum = icmpv6_udp_checksum(&ipv6_packet)?;
-            replace_u16(&mut ipv6_packet, IPV6_PAYLOAD_START + 2, &new_checksum)?;
-        },
-        // UDP (DHCPv6)
-        //
-        // * https://datatracker.ietf.org/doc/html/rfc8415
-        17 => {
-            const UDP_FIXED_HEADER_SIZE: usize = 8;
+            // Generate custom PREF64
+            if let Some(pref64) = &config.pref64 {
+                ra.options.push(
+                    pref64_option(
+                        pref64.prefix,
+                        pref64.prefix_length,
+                        pref64.lifetime.unwrap_or(INJECTED_OPTION_LIFETIME_DEFAULT),
+                    ).ok_or(ModifyError::Config)?,
+                );
+                report.pref64 = true;
+            }
 
-            // Confirm it's reply
-            if *ipv6_packet.get(IPV6_PAYLOAD_START + UDP_FIXED_HEADER_SIZE)? != 7 {
-                return None;
+            // Generate custom route information
+            for route in &config.routes {
+                ra.options.push(
+                    route_information_option(
+                        route.prefix,
+                        route.prefix_length,
+                        route.preference,
+                        route.lifetime.unwrap_or(INJECTED_OPTION_LIFETIME_DEFAULT),
+                    ).ok_or(ModifyError::Config)?,
+                );
+                report.route_information = true;
             }
 
-            // Copy + filter out options
-            const OPT_DNS: &[u8] = &[0x00, 0x17];
-            const DHCP_FIXED_HEADER_SIZE: usize = 4;
-            const DHCP_OPTIONS_START: usize = IPV6_PAYLOAD_START + UDP_FIXED_HEADER_SIZE + DHCP_FIXED_HEADER_SIZE;
-            let mut at_option_start = DHCP_OPTIONS_START;
-            let mut new_options = vec![];
-            let mut found_dns = false;
-            new_options.reserve(ipv6_packet.len() - IPV6_PAYLOAD_START);
-            loop {
-                if at_option_start == ipv6_packet.len() {
-                    break;
-                }
-                let at_option_type = ipv6_packet.get(at_option_start .. at_option_start + 2)?;
-                let at_option_length = read_u16(&ipv6_packet, at_option_start + 2)? as usize + 4;
-                shed!{
-                    'next_option _;
-                    if at_option_type == OPT_DNS {
-                        // Drop existing DNS
-                        found_dns = true;
-                        break 'next_option;
-                    }
-                    // Keep anything not DNS
-                    new_options.extend_from_slice(ipv6_packet.get(at_option_start .. at_option_start + at_option_length)?);
+            // Generate custom DNSSL
+            if let Some(search_domains) = &config.search_domains {
+                let names = encode_dns_names(&search_domains.domains).ok_or(ModifyError::Config)?;
+                if names.len() > DNSSL_MAX_NAMES_SIZE {
+                    return Err(ModifyError::Config);
                 }
-                at_option_start += at_option_length;
+                ra.options.push(RaOption::Dnssl {
+                    lifetime: search_domains.lifetime.unwrap_or(INJECTED_OPTION_LIFETIME_DEFAULT),
+                    names: names,
+                });
+                report.dnssl = true;
             }
-            if !found_dns {
-                return Some(source.to_vec());
+
+            // Generate custom captive portal
+            if let Some(uri) = &config.captive_portal {
+                if uri.len() > CAPTIVE_PORTAL_MAX_URI_SIZE {
+                    return Err(ModifyError::Config);
+                }
+                ra.options.push(RaOption::CaptivePortal { uri: uri.clone() });
+                report.captive_portal = true;
             }
 
-            // Generate custom DNS option
-            new_options.extend_from_slice(OPT_DNS);
-            new_options.extend_from_slice(
-                // Length (16 bytes, 1 ip)
-                &[0x00, 0x10],
-            );
-            let ip_bytes = ip.octets();
-            new_options.extend(ip_bytes);
-
-            // Replace options
-            splice(&mut ipv6_packet, DHCP_OPTIONS_START, None, &new_options)?;
-
-            // Update payload length in udp header
-            let new_len = UDP_FIXED_HEADER_SIZE + DHCP_FIXED_HEADER_SIZE + new_options.len();
-            replace_u16(&mut ipv6_packet, IPV6_PAYLOAD_START + 4, &(new_len as u16).to_be_bytes())?;
-
-            // Update payload length in ipv6 header
-            replace_u16(&mut ipv6_packet, 4, &(new_len as u16).to_be_bytes())?;
-
-            // Recalc checksum
-            ipv6_packet.get_mut(IPV6_PAYLOAD_START + 6 .. IPV6_PAYLOAD_START + 8)?.fill(0);
-            let new_checksum = icmpv6_udp_checksum(&ipv6_packet)?;
-            replace_u16(&mut ipv6_packet, IPV6_PAYLOAD_START + 6, &new_checksum)?;
+            if let Some(trace) = &mut report.trace {
+                trace.options_removed.extend(removed);
+                trace.options_added.extend(ra.options[kept..].iter().map(|o| o.type_() as u16));
+            }
         },
-        _ => {
-            return None;
+        // UDP (DHCPv6)
+        //
+        // * https://datatracker.ietf.org/doc/html/rfc8415
+        Ipv6Payload::Udp(udp) => {
+            let UdpPayload::Dhcpv6(dhcpv6) = &mut udp.payload else {
+                return Err(ModifyError::NotDhcpv6);
+            };
+
+            if !modify_dhcpv6(dhcpv6, ips, config, report)? {
+                return Ok(source.to_vec());
+            }
         },
     }
 
     // Done
-    return Some(ipv6_packet);
+    return ipv6_packet.serialize().ok_or(ModifyError::Config);
+}
+
+/// Rewrites a cached upstream RA to send to all nodes, after the resolver addresses
+/// change or in response to a router solicitation. `withdrawn` resolvers not in
+/// `ips` are advertised with a zero lifetime so clients stop using them
+/// immediately, if the RA carries RDNSS.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc8106#section-5.1
+pub fn unsolicited_ra(
+    source: &[u8],
+    ips: &[Ipv6Addr],
+    withdrawn: &[Ipv6Addr],
+    config: &ModifyConfig,
+    report: &mut ModifyReport,
+) -> Result<Vec<u8>, ModifyError> {
+    let modified = modify(source, ips, config, report)?;
+    let mut ipv6_packet = Ipv6Packet::parse(&modified).ok_or(ModifyError::Parse)?;
+    let Ipv6Payload::Icmpv6(icmpv6) = &mut ipv6_packet.payload else {
+        return Err(ModifyError::NotRa);
+    };
+    let Icmpv6Body::RouterAdvertisement(ra) = &mut icmpv6.body else {
+        return Err(ModifyError::NotRa);
+    };
+    let withdrawn = withdrawn.iter().filter(|a| !ips.contains(a)).copied().collect::<Vec<_>>();
+    if report.rdnss && !withdrawn.is_empty() {
+        ra.options.push(RaOption::Rdnss {
+            lifetime: 0,
+            addresses: withdrawn[.. withdrawn.len().min(RDNSS_MAX_ADDRESSES)].to_vec(),
+        });
+    }
+
+    // Cached RAs may have been unicast in reply to a solicitation
+    //
+    // * https://datatracker.ietf.org/doc/html/rfc4861#section-6.2.4
+    ipv6_packet.destination = IPV6_ALL_NODES;
+    return ipv6_packet.serialize().ok_or(ModifyError::Config);
 }
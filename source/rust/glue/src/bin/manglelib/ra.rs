@@ -0,0 +1,364 @@
+use {
+    aargvark::Aargvark,
+    super::{
+        read_ipv6,
+        read_u16,
+        read_u32,
+    },
+    std::net::Ipv6Addr,
+};
+
+// RA
+//
+// * https://datatracker.ietf.org/doc/html/rfc4861#section-4.2
+const RA_FIXED_HEADER_SIZE: usize = 12;
+pub const RA_FLAG_MANAGED: u8 = 0x80;
+pub const RA_FLAG_OTHER: u8 = 0x40;
+// * https://datatracker.ietf.org/doc/html/rfc4191#section-2.2
+const RA_PREFERENCE_MASK: u8 = 0x18;
+pub const OPT_PREFIX_INFORMATION: u8 = 3;
+pub const OPT_MTU: u8 = 5;
+pub const OPT_ROUTE_INFORMATION: u8 = 24;
+pub const OPT_RDNSS: u8 = 25;
+pub const OPT_DNSSL: u8 = 31;
+// * https://datatracker.ietf.org/doc/html/rfc8910#section-2.3
+pub const OPT_CAPTIVE_PORTAL: u8 = 37;
+pub const OPT_PREF64: u8 = 38;
+pub const PIO_FLAG_ON_LINK: u8 = 0x80;
+pub const PIO_FLAG_AUTONOMOUS: u8 = 0x40;
+// * https://datatracker.ietf.org/doc/html/rfc8781#section-4
+const PREF64_PLC_LENGTHS: [u8; 6] = [96, 64, 56, 48, 40, 32];
+const PREF64_MAX_SCALED_LIFETIME: u32 = 0x1fff;
+// Option length is a single byte, in units of 8 octets
+const OPTION_MAX_SIZE: usize = 255 * 8;
+/// After the type, length, reserved, and lifetime fields
+pub const RDNSS_MAX_ADDRESSES: usize = (OPTION_MAX_SIZE - 8) / 16;
+/// After the type, length, reserved, and lifetime fields
+pub const DNSSL_MAX_NAMES_SIZE: usize = OPTION_MAX_SIZE - 8;
+/// After the type and length fields
+pub const CAPTIVE_PORTAL_MAX_URI_SIZE: usize = OPTION_MAX_SIZE - 2;
+
+/// Default router or route preference.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc4191#section-2.1
+#[derive(Aargvark, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RouterPreference {
+    High,
+    Medium,
+    Low,
+}
+
+impl RouterPreference {
+    fn bits(self) -> u8 {
+        match self {
+            RouterPreference::High => return 0x08,
+            RouterPreference::Medium => return 0x00,
+            RouterPreference::Low => return 0x18,
+        }
+    }
+}
+
+/// Bytes of the route information prefix field, which is truncated to 0, 8, or 16
+/// bytes depending on the prefix length.
+///
+/// * https://datatracker.ietf.org/doc/html/rfc4191#section-2.3
+fn route_prefix_size(prefix_length: u8) -> usize {
+    match prefix_length {
+        0 => return 0,
+        1 ..= 64 => return 8,
+        _ => return 16,
+    }
+}
+
+pub struct RouterAdvertisement {
+    pub cur_hop_limit: u8,
+    pub flags: u8,
+    pub router_lifetime: u16,
+    pub reachable_time: u32,
+    pub retrans_timer: u32,
+    pub options: Vec<RaOption>,
+}
+
+/// Options are only parsed into a typed variant if they'd serialize back to the
+/// same bytes, anything else (unknown, nonzero reserved fields) is kept raw.
+pub enum RaOption {
+    // * https://datatracker.ietf.org/doc/html/rfc4861#section-4.6.2
+    PrefixInformation {
+        prefix_length: u8,
+        /// L, A, and any other flags
+        flags: u8,
+        /// Seconds
+        valid_lifetime: u32,
+        /// Seconds
+        preferred_lifetime: u32,
+        /// Reserved2, kept so it serializes back to the same bytes
+        reserved: u32,
+        prefix: Ipv6Addr,
+    },
+    // * https://datatracker.ietf.org/doc/html/rfc4861#section-4.6.4
+    Mtu(u32),
+    // * https://datatracker.ietf.org/doc/html/rfc4191#section-2.3
+    //
+    // Only parsed if the option is the shortest length that fits the prefix, so it
+    // serializes back to the same bytes.
+    RouteInformation {
+        prefix_length: u8,
+        /// Route preference and reserved bits
+        flags: u8,
+        /// Seconds
+        lifetime: u32,
+        prefix: Ipv6Addr,
+    },
+    // * https://datatracker.ietf.org/doc/html/rfc8106#section-5.1
+    Rdnss {
+        lifetime: u32,
+        addresses: Vec<Ipv6Addr>,
+    },
+    // * https://datatracker.ietf.org/doc/html/rfc8106#section-5.2
+    //
+    // Only generated, upstream DNSSL options are parsed as unknown.
+    Dnssl {
+        lifetime: u32,
+        /// Concatenated names in dns wire format, padded on serialization
+        names: Vec<u8>,
+    },
+    // * https://datatracker.ietf.org/doc/html/rfc8910#section-2.3
+    //
+    // Only generated, upstream captive portal options are parsed as unknown.
+    CaptivePortal {
+        /// Padded with NULs on serialization
+        uri: String,
+    },
+    // * https://datatracker.ietf.org/doc/html/rfc8781#section-4
+    Pref64 {
+        /// Seconds, a multiple of 8
+        lifetime: u16,
+        prefix: Ipv6Addr,
+        prefix_length: u8,
+    },
+    Unknown {
+        type_: u8,
+        /// Everything after the type and length bytes
+        body: Vec<u8>,
+    },
+}
+
+impl RouterAdvertisement {
+    /// Source is the ICMPv6 body (after the checksum).
+    pub fn parse(source: &[u8]) -> Option<Self> {
+        let mut options = vec![];
+        let mut at_option_start = RA_FIXED_HEADER_SIZE;
+        while at_option_start < source.len() {
+            let option_length = *source.get(at_option_start + 1)? as usize * 8;
+            if option_length == 0 {
+                // Invalid per rfc, would never advance
+                return None;
+            }
+            options.push(RaOption::parse(source.get(at_option_start .. at_option_start + option_length)?));
+            at_option_start += option_length;
+        }
+        return Some(RouterAdvertisement {
+            cur_hop_limit: *source.first()?,
+            flags: *source.get(1)?,
+            router_lifetime: read_u16(source, 2)?,
+            reachable_time: read_u32(source, 4)?,
+            retrans_timer: read_u32(source, 8)?,
+            options: options,
+        });
+    }
+
+    pub fn set_preference(&mut self, preference: RouterPreference) {
+        self.flags = (self.flags & !RA_PREFERENCE_MASK) | preference.bits();
+    }
+
+    pub fn serialize(&self, out: &mut Vec<u8>) {
+        out.push(self.cur_hop_limit);
+        out.push(self.flags);
+        out.extend_from_slice(&self.router_lifetime.to_be_bytes());
+        out.extend_from_slice(&self.reachable_time.to_be_bytes());
+        out.extend_from_slice(&self.retrans_timer.to_be_bytes());
+        for option in &self.options {
+            option.serialize(out);
+        }
+    }
+}
+
+impl RaOption {
+    /// Source is the whole option including type and length.
+    fn parse(source: &[u8]) -> Self {
+        let type_ = source[0];
+        let body = &source[2..];
+        match type_ {
+            OPT_PREFIX_INFORMATION if body.len() == 30 => {
+                return RaOption::PrefixInformation {
+                    prefix_length: body[0],
+                    flags: body[1],
+                    valid_lifetime: read_u32(body, 2).unwrap(),
+                    preferred_lifetime: read_u32(body, 6).unwrap(),
+                    reserved: read_u32(body, 10).unwrap(),
+                    prefix: read_ipv6(body, 14).unwrap(),
+                };
+            },
+            OPT_MTU if body.len() == 6 && body[0 .. 2] == [0, 0] => {
+                return RaOption::Mtu(read_u32(body, 2).unwrap());
+            },
+            OPT_ROUTE_INFORMATION if body.len() >= 6 && body[0] <= 128 &&
+                body.len() == 6 + route_prefix_size(body[0]) => {
+                let mut prefix = [0u8; 16];
+                prefix[.. body.len() - 6].copy_from_slice(&body[6..]);
+                return RaOption::RouteInformation {
+                    prefix_length: body[0],
+                    flags: body[1],
+                    lifetime: read_u32(body, 2).unwrap(),
+                    prefix: Ipv6Addr::from(prefix),
+                };
+            },
+            OPT_RDNSS if body.len() >= 22 && (body.len() - 6) % 16 == 0 && body[0 .. 2] == [0, 0] => {
+                return RaOption::Rdnss {
+                    lifetime: read_u32(body, 2).unwrap(),
+                    addresses: body[6..].chunks_exact(16).map(|a| read_ipv6(a, 0).unwrap()).collect(),
+                };
+            },
+            OPT_PREF64 if body.len() == 14 && (body[1] & 0x07) < PREF64_PLC_LENGTHS.len() as u8 => {
+                let mut prefix = [0u8; 16];
+                prefix[.. 12].copy_from_slice(&body[2 .. 14]);
+                return RaOption::Pref64 {
+                    lifetime: read_u16(body, 0).unwrap() & !0x07,
+                    prefix: Ipv6Addr::from(prefix),
+                    prefix_length: PREF64_PLC_LENGTHS[(body[1] & 0x07) as usize],
+                };
+            },
+            _ => {
+                return RaOption::Unknown {
+                    type_: type_,
+                    body: body.to_vec(),
+                };
+            },
+        }
+    }
+
+    /// The prefix and prefix length of a route information option, also when it's
+    /// kept unparsed because the encoding isn't the shortest.
+    pub fn route_prefix(&self) -> Option<(Ipv6Addr, u8)> {
+        match self {
+            RaOption::RouteInformation { prefix_length, prefix, .. } => return Some((*prefix, *prefix_length)),
+            RaOption::Unknown { type_: OPT_ROUTE_INFORMATION, body } if body.len() >= 6 && body[0] <= 128 => {
+                let mut prefix = [0u8; 16];
+                let prefix_bytes = &body[6 .. body.len().min(22)];
+                prefix[.. prefix_bytes.len()].copy_from_slice(prefix_bytes);
+                return Some((Ipv6Addr::from(prefix), body[0]));
+            },
+            _ => return None,
+        }
+    }
+
+    pub fn type_(&self) -> u8 {
+        match self {
+            RaOption::PrefixInformation { .. } => return OPT_PREFIX_INFORMATION,
+            RaOption::Mtu(_) => return OPT_MTU,
+            RaOption::RouteInformation { .. } => return OPT_ROUTE_INFORMATION,
+            RaOption::Rdnss { .. } => return OPT_RDNSS,
+            RaOption::Dnssl { .. } => return OPT_DNSSL,
+            RaOption::CaptivePortal { .. } => return OPT_CAPTIVE_PORTAL,
+            RaOption::Pref64 { .. } => return OPT_PREF64,
+            RaOption::Unknown { type_, .. } => return *type_,
+        }
+    }
+
+    fn serialize(&self, out: &mut Vec<u8>) {
+        let start = out.len();
+        out.push(self.type_());
+
+        // Length, filled in after padding
+        out.push(0);
+        match self {
+            RaOption::PrefixInformation {
+                prefix_length,
+                flags,
+                valid_lifetime,
+                preferred_lifetime,
+                reserved,
+                prefix,
+            } => {
+                out.push(*prefix_length);
+                out.push(*flags);
+                out.extend_from_slice(&valid_lifetime.to_be_bytes());
+                out.extend_from_slice(&preferred_lifetime.to_be_bytes());
+                out.extend_from_slice(&reserved.to_be_bytes());
+                out.extend_from_slice(&prefix.octets());
+            },
+            RaOption::Mtu(mtu) => {
+                out.extend_from_slice(&[0, 0]);
+                out.extend_from_slice(&mtu.to_be_bytes());
+            },
+            RaOption::RouteInformation { prefix_length, flags, lifetime, prefix } => {
+                out.push(*prefix_length);
+                out.push(*flags);
+                out.extend_from_slice(&lifetime.to_be_bytes());
+                out.extend_from_slice(&prefix.octets()[.. route_prefix_size(*prefix_length)]);
+            },
+            RaOption::Rdnss { lifetime, addresses } => {
+                out.extend_from_slice(&[0, 0]);
+                out.extend_from_slice(&lifetime.to_be_bytes());
+                for address in addresses {
+                    out.extend_from_slice(&address.octets());
+                }
+            },
+            RaOption::Dnssl { lifetime, names } => {
+                out.extend_from_slice(&[0, 0]);
+                out.extend_from_slice(&lifetime.to_be_bytes());
+                out.extend_from_slice(names);
+            },
+            RaOption::CaptivePortal { uri } => {
+                out.extend_from_slice(uri.as_bytes());
+            },
+            RaOption::Pref64 { lifetime, prefix, prefix_length } => {
+                // Scaled lifetime is the top 13 bits, PLC the bottom 3 - a multiple of 8 masks
+                // out the PLC bits
+                let plc = PREF64_PLC_LENGTHS.iter().position(|l| l == prefix_length).unwrap() as u16;
+                out.extend_from_slice(&(lifetime | plc).to_be_bytes());
+                out.extend_from_slice(&prefix.octets()[.. 12]);
+            },
+            RaOption::Unknown { body, .. } => {
+                out.extend_from_slice(body);
+            },
+        }
+        while (out.len() - start) % 8 != 0 {
+            out.push(0);
+        }
+        out[start + 1] = ((out.len() - start) / 8) as u8;
+    }
+}
+
+/// Returns None if the prefix length can't be represented in a PREF64 option.
+pub fn pref64_option(prefix: Ipv6Addr, prefix_length: u8, lifetime: u32) -> Option<RaOption> {
+    if !PREF64_PLC_LENGTHS.contains(&prefix_length) {
+        return None;
+    }
+
+    // Round up so the prefix doesn't expire before the requested lifetime
+    let scaled_lifetime = lifetime.div_ceil(8).min(PREF64_MAX_SCALED_LIFETIME);
+    return Some(RaOption::Pref64 {
+        lifetime: (scaled_lifetime * 8) as u16,
+        prefix: Ipv6Addr::from(u128::from(prefix) & (!0u128 << (128 - prefix_length))),
+        prefix_length: prefix_length,
+    });
+}
+
+/// Returns None if the prefix length is over 128.
+pub fn route_information_option(
+    prefix: Ipv6Addr,
+    prefix_length: u8,
+    preference: RouterPreference,
+    lifetime: u32,
+) -> Option<RaOption> {
+    if prefix_length > 128 {
+        return None;
+    }
+    return Some(RaOption::RouteInformation {
+        prefix_length: prefix_length,
+        flags: preference.bits(),
+        lifetime: lifetime,
+        prefix: Ipv6Addr::from(u128::from(prefix) & (!0u128).checked_shl(128 - prefix_length as u32).unwrap_or(0)),
+    });
+}
@@ -144,6 +144,15 @@ fn test_checksum_roll_ex4() {
     assert_eq!(checksum_finish(sum32), [!0xf0, !0xeb]);
 }
 
+#[test]
+fn test_checksum_roll_carry() {
+    let mut sum32 = 0u32;
+
+    // Folding once carries again
+    checksum_roll(&mut sum32, &[0xff, 0xff, 0xff, 0xff, 0x01, 0x00]);
+    assert_eq!(checksum_finish(sum32), [0xfe, 0xff]);
+}
+
 #[test]
 fn test_checksum_ex1() {
     assert_eq!(icmpv6_udp_checksum(PAYLOAD_RA1).unwrap(), [0xfd, 0x40]);
@@ -0,0 +1,895 @@
+use {
+    crate::manglelib::{
+        maybe_ra_or_dhcpv6_server,
+        modify,
+        ModifyConfig,
+        ModifyReport,
+    },
+    std::net::Ipv6Addr,
+};
+
+const PAYLOAD_RA_HOP_BY_HOP: &[u8] = &[
+    // ipv6
+    0x6b,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x28,
+    0x00,
+    0xff,
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x4a,
+    0x2e,
+    0x72,
+    0xff,
+    0xfe,
+    0x63,
+    0x7d,
+    0x10,
+    0xff,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x01,
+    // Hop-by-hop options, PadN
+    0x3a,
+    0x00,
+    0x01,
+    0x04,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    // icmpv6 ra
+    0x86,
+    0x00,
+    0xfd,
+    0x40,
+    0x40,
+    0xc0,
+    0x07,
+    0x08,
+    0x00,
+    0x04,
+    0x93,
+    0xe0,
+    0x00,
+    0x00,
+    0x27,
+    0x10,
+    0x01,
+    0x01,
+    0x48,
+    0x2e,
+    0x72,
+    0x63,
+    0x7d,
+    0x10,
+    // mtu option start
+    0x05,
+    0x01,
+    0x00,
+    0x00,
+    // mtu
+    0x00,
+    0x00,
+    0x05,
+    0xdc,
+];
+
+pub const PAYLOAD_RA_ROUTING: &[u8] = &[
+    // ipv6
+    0x6b,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x38,
+    0x2b,
+    0xff,
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x4a,
+    0x2e,
+    0x72,
+    0xff,
+    0xfe,
+    0x63,
+    0x7d,
+    0x10,
+    0xff,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x01,
+    // Routing type 2, 1 segment left
+    0x3a,
+    0x02,
+    0x02,
+    0x01,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    // Final destination
+    0x20,
+    0x01,
+    0x0d,
+    0xb8,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x01,
+    // icmpv6 ra
+    0x86,
+    0x00,
+    0xce,
+    0x8a,
+    0x40,
+    0xc0,
+    0x07,
+    0x08,
+    0x00,
+    0x04,
+    0x93,
+    0xe0,
+    0x00,
+    0x00,
+    0x27,
+    0x10,
+    0x01,
+    0x01,
+    0x48,
+    0x2e,
+    0x72,
+    0x63,
+    0x7d,
+    0x10,
+    // mtu option start
+    0x05,
+    0x01,
+    0x00,
+    0x00,
+    // mtu
+    0x00,
+    0x00,
+    0x05,
+    0xdc,
+];
+
+pub const PAYLOAD_DHCP_DESTINATION_OPTIONS: &[u8] = &[
+    // IPv6
+    0x6b,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x99,
+    0x3c,
+    0x01,
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x40,
+    0xff,
+    0xfe,
+    0x12,
+    0x20,
+    0x0a,
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0xb2,
+    0x6e,
+    0xbf,
+    0xff,
+    0xfe,
+    0x39,
+    0xbf,
+    0x7b,
+    // Destination options, PadN
+    0x11,
+    0x00,
+    0x01,
+    0x04,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    // UDP
+    0x02,
+    0x23,
+    0x02,
+    0x22,
+    0x00,
+    0x91,
+    // Checksum
+    0xe7,
+    0xfc,
+    // DHCPv6
+    0x07,
+    0x56,
+    0x20,
+    0xfd,
+    0x00,
+    0x02,
+    0x00,
+    0x0a,
+    0x00,
+    0x03,
+    0x00,
+    0x01,
+    0x00,
+    0x19,
+    0xaa,
+    0xbc,
+    0xfa,
+    0x1b,
+    0x00,
+    0x01,
+    0x00,
+    0x0e,
+    0x00,
+    0x02,
+    0x00,
+    0x00,
+    0xab,
+    0x11,
+    0xfc,
+    0x72,
+    0xdb,
+    0x43,
+    0x6a,
+    0xc5,
+    0xca,
+    0x1f,
+    0x00,
+    0x17,
+    0x00,
+    0x20,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x7f,
+    0x01,
+    0x00,
+    0x0b,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x03,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x7f,
+    0x01,
+    0x00,
+    0x0a,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x03,
+    0x00,
+    0x18,
+    0x00,
+    0x19,
+    0x0a,
+    0x66,
+    0x6c,
+    0x65,
+    0x74,
+    0x73,
+    0x2d,
+    0x65,
+    0x61,
+    0x73,
+    0x74,
+    0x02,
+    0x6a,
+    0x70,
+    0x00,
+    0x05,
+    0x69,
+    0x70,
+    0x74,
+    0x76,
+    0x66,
+    0x02,
+    0x6a,
+    0x70,
+    0x00,
+    0x00,
+    0x1f,
+    0x00,
+    0x20,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x11,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x0b,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x11,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x0a,
+];
+
+#[test]
+fn test_ra_hop_by_hop() {
+    let got = modify(
+        PAYLOAD_RA_HOP_BY_HOP,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig {
+            mtu: Some(13),
+            ..Default::default()
+        },
+        &mut ModifyReport::default(),
+    ).unwrap();
+    let mut want = vec![
+        // ipv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x28,
+        0x00,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x4a,
+        0x2e,
+        0x72,
+        0xff,
+        0xfe,
+        0x63,
+        0x7d,
+        0x10,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // Hop-by-hop options, PadN
+        0x3a,
+        0x00,
+        0x01,
+        0x04,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        // icmpv6 ra
+        0x86,
+        0x00,
+        0x03,
+        0x10,
+        0x40,
+        0xc0,
+        0x07,
+        0x08,
+        0x00,
+        0x04,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        0x01,
+        0x01,
+        0x48,
+        0x2e,
+        0x72,
+        0x63,
+        0x7d,
+        0x10,
+        // mtu option start
+        0x05,
+        0x01,
+        0x00,
+        0x00,
+        // mtu
+        0x00,
+        0x00,
+        0x00,
+        0x0d,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_ra_routing() {
+    let got = modify(
+        PAYLOAD_RA_ROUTING,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig {
+            mtu: Some(13),
+            ..Default::default()
+        },
+        &mut ModifyReport::default(),
+    ).unwrap();
+    let mut want = vec![
+        // ipv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x38,
+        0x2b,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x4a,
+        0x2e,
+        0x72,
+        0xff,
+        0xfe,
+        0x63,
+        0x7d,
+        0x10,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // Routing type 2, 1 segment left
+        0x3a,
+        0x02,
+        0x02,
+        0x01,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        // Final destination
+        0x20,
+        0x01,
+        0x0d,
+        0xb8,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // icmpv6 ra
+        0x86,
+        0x00,
+        0xd4,
+        0x59,
+        0x40,
+        0xc0,
+        0x07,
+        0x08,
+        0x00,
+        0x04,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        0x01,
+        0x01,
+        0x48,
+        0x2e,
+        0x72,
+        0x63,
+        0x7d,
+        0x10,
+        // mtu option start
+        0x05,
+        0x01,
+        0x00,
+        0x00,
+        // mtu
+        0x00,
+        0x00,
+        0x00,
+        0x0d,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_dhcp_destination_options() {
+    let got = modify(
+        PAYLOAD_DHCP_DESTINATION_OPTIONS,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig::default(),
+        &mut ModifyReport::default(),
+    ).unwrap();
+    let mut want = vec![
+        // IPv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        // Length
+        0x00,
+        0x89,
+        0x3c,
+        0x01,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0xb2,
+        0x6e,
+        0xbf,
+        0xff,
+        0xfe,
+        0x39,
+        0xbf,
+        0x7b,
+        // Destination options, PadN
+        0x11,
+        0x00,
+        0x01,
+        0x04,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        // UDP
+        0x02,
+        0x23,
+        0x02,
+        0x22,
+        // UDP length
+        0x00,
+        0x81,
+        // Checksum
+        0xe6,
+        0xc9,
+        // DHCPv6
+        0x07,
+        0x56,
+        0x20,
+        0xfd,
+        0x00,
+        0x02,
+        0x00,
+        0x0a,
+        0x00,
+        0x03,
+        0x00,
+        0x01,
+        0x00,
+        0x19,
+        0xaa,
+        0xbc,
+        0xfa,
+        0x1b,
+        0x00,
+        0x01,
+        0x00,
+        0x0e,
+        0x00,
+        0x02,
+        0x00,
+        0x00,
+        0xab,
+        0x11,
+        0xfc,
+        0x72,
+        0xdb,
+        0x43,
+        0x6a,
+        0xc5,
+        0xca,
+        0x1f,
+        0x00,
+        0x18,
+        0x00,
+        0x19,
+        0x0a,
+        0x66,
+        0x6c,
+        0x65,
+        0x74,
+        0x73,
+        0x2d,
+        0x65,
+        0x61,
+        0x73,
+        0x74,
+        0x02,
+        0x6a,
+        0x70,
+        0x00,
+        0x05,
+        0x69,
+        0x70,
+        0x74,
+        0x76,
+        0x66,
+        0x02,
+        0x6a,
+        0x70,
+        0x00,
+        0x00,
+        0x1f,
+        0x00,
+        0x20,
+        0x24,
+        0x04,
+        0x01,
+        0xa8,
+        0x11,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x0b,
+        0x24,
+        0x04,
+        0x01,
+        0xa8,
+        0x11,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x0a,
+        // DNS option
+        0x00,
+        0x17,
+        // Length 16
+        0x00,
+        0x10,
+        // IP
+        0x00,
+        0x01,
+        0x00,
+        0x02,
+        0x00,
+        0x03,
+        0x00,
+        0x04,
+        0x00,
+        0x05,
+        0x00,
+        0x06,
+        0x00,
+        0x07,
+        0x00,
+        0x08,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_maybe_ra_or_dhcpv6_server() {
+    let check = |packet: &[u8]| {
+        assert!(modify(
+            packet,
+            &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+            &ModifyConfig::default(),
+            &mut ModifyReport::default(),
+        ).is_err());
+        return maybe_ra_or_dhcpv6_server(packet);
+    };
+
+    // First fragment of an RA: the hop-by-hop header swapped for a fragment header
+    // with the more fragments flag
+    let mut fragment = PAYLOAD_RA_HOP_BY_HOP.to_vec();
+    fragment[6] = 44;
+    fragment[40 .. 48].copy_from_slice(&[0x3a, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01]);
+    assert!(check(&fragment));
+
+    // A later fragment, the upper layer header isn't there
+    fragment[40 .. 48].copy_from_slice(&[0x3a, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x01]);
+    assert!(check(&fragment));
+
+    // Not an RA
+    let mut echo = PAYLOAD_RA_HOP_BY_HOP.to_vec();
+    echo[48] = 0x80;
+    assert!(!check(&echo));
+
+    // DHCPv6 reply cut off after the UDP source port
+    let mut truncated = PAYLOAD_DHCP_DESTINATION_OPTIONS[.. 50].to_vec();
+    assert!(check(&truncated));
+
+    // From the client port
+    truncated[49] = 0x22;
+    assert!(!check(&truncated));
+}
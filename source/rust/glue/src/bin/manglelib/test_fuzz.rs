@@ -0,0 +1,475 @@
+use {
+    crate::manglelib::{
+        icmpv6_udp_checksum,
+        ipv6::{
+            HeaderChain,
+            Ipv6Packet,
+            IPV6_FIXED_HEADER_SIZE,
+            NEXT_HEADER_ICMPV6,
+            NEXT_HEADER_UDP,
+        },
+        modify,
+        ra::RouterPreference,
+        read_u16,
+        ModifyConfig,
+        ModifyError,
+        ModifyReport,
+        PioConfig,
+        Pref64Config,
+        RaHeaderConfig,
+        RouteConfig,
+        SearchDomainsConfig,
+    },
+    rand::{
+        rngs::StdRng,
+        seq::SliceRandom,
+        Rng,
+        SeedableRng,
+    },
+    std::{
+        net::Ipv6Addr,
+        panic::{
+            catch_unwind,
+            AssertUnwindSafe,
+        },
+    },
+};
+
+// Property tests: random and mutated packets must never panic or hang `modify`,
+// and any modified output must be a well formed packet that `modify` leaves alone
+// when run again.
+const ITERATIONS: usize = 4000;
+const RA_OPTION_TYPES: [u8; 8] = [1, 3, 5, 24, 25, 31, 38, 0xfe];
+const DHCPV6_MSG_TYPES: [u8; 6] = [2, 7, 11, 12, 13, 0xfe];
+const DHCPV6_OPTION_CODES: [u16; 12] = [1, 2, 3, 4, 5, 9, 17, 23, 24, 25, 26, 0xfffe];
+
+fn seeds() -> Vec<&'static [u8]> {
+    return vec![
+        super::test_ra_inject_mtu::PAYLOAD_RA1,
+        super::test_ra_modify_mtu::PAYLOAD_RA1,
+        super::test_ra_pref64::PAYLOAD_RA_PREF64,
+        super::test_extension_headers::PAYLOAD_RA_ROUTING,
+        super::test_extension_headers::PAYLOAD_DHCP_DESTINATION_OPTIONS,
+        super::test_modify_dhcp_ex1::PAYLOAD_DHCP1,
+        super::test_modify_dhcp_nested::PAYLOAD_DHCP_NESTED,
+        super::test_dhcp_message_types::PAYLOAD_DHCP_RELAY_REPLY,
+    ];
+}
+
+fn random_bytes(rng: &mut StdRng, length: usize) -> Vec<u8> {
+    return (0 .. length).map(|_| rng.gen()).collect();
+}
+
+fn ipv6_packet(next_header: u8, upper: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x60, 0, 0, 0];
+    out.extend_from_slice(&(upper.len() as u16).to_be_bytes());
+    out.push(next_header);
+    out.push(255);
+    out.extend_from_slice(&Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1).octets());
+    out.extend_from_slice(&Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1).octets());
+    out.extend_from_slice(upper);
+    fix_lengths(&mut out);
+    return out;
+}
+
+/// Make length fields and the checksum match the (mutated) packet so mutations
+/// reach the upper layer parsers.
+fn fix_lengths(packet: &mut [u8]) {
+    if packet.len() < IPV6_FIXED_HEADER_SIZE || packet.len() - IPV6_FIXED_HEADER_SIZE > u16::MAX as usize {
+        return;
+    }
+    let payload_length = (packet.len() - IPV6_FIXED_HEADER_SIZE) as u16;
+    packet[4 .. 6].copy_from_slice(&payload_length.to_be_bytes());
+    let Some(chain) = HeaderChain::parse(packet) else {
+        return;
+    };
+    let upper_start = packet.len() - chain.upper.len();
+    let upper_length = chain.upper.len();
+    let checksum_offset = match chain.next_header {
+        NEXT_HEADER_ICMPV6 if upper_length >= 4 => 2,
+        NEXT_HEADER_UDP if upper_length >= 8 => {
+            packet[upper_start + 4 .. upper_start + 6].copy_from_slice(&(upper_length as u16).to_be_bytes());
+            6
+        },
+        _ => return,
+    };
+    let checksum_start = upper_start + checksum_offset;
+    packet[checksum_start .. checksum_start + 2].copy_from_slice(&[0, 0]);
+    let checksum = icmpv6_udp_checksum(packet).unwrap();
+    packet[checksum_start .. checksum_start + 2].copy_from_slice(&checksum);
+}
+
+fn generate_ra(rng: &mut StdRng) -> Vec<u8> {
+    // ICMPv6 type, code, checksum, then RA fixed fields
+    let mut upper = vec![134, 0, 0, 0];
+    upper.extend(random_bytes(rng, 12));
+    for _ in 0 .. rng.gen_range(0 .. 6) {
+        let type_ = *RA_OPTION_TYPES.choose(rng).unwrap();
+        match type_ {
+            25 if rng.gen_bool(0.5) => {
+                let count = rng.gen_range(1 .. 4);
+                upper.extend_from_slice(&[type_, 1 + count * 2, 0, 0]);
+                upper.extend(random_bytes(rng, 4 + count as usize * 16));
+            },
+            3 if rng.gen_bool(0.5) => {
+                upper.extend_from_slice(&[type_, 4, rng.gen_range(0 ..= 128), rng.gen()]);
+                upper.extend(random_bytes(rng, 28));
+            },
+            5 if rng.gen_bool(0.5) => {
+                upper.extend_from_slice(&[type_, 1, 0, 0]);
+                upper.extend(random_bytes(rng, 4));
+            },
+            24 if rng.gen_bool(0.5) => {
+                // Shortest length for the prefix length
+                let prefix_length = rng.gen_range(0 ..= 128);
+                let length = match prefix_length {
+                    0 => 1,
+                    1 ..= 64 => 2,
+                    _ => 3,
+                };
+                upper.extend_from_slice(&[type_, length, prefix_length]);
+                upper.extend(random_bytes(rng, length as usize * 8 - 3));
+            },
+            _ => {
+                // Includes 0 length, which is invalid
+                let length = rng.gen_range(0 .. 4u8);
+                upper.extend_from_slice(&[type_, length]);
+                upper.extend(random_bytes(rng, (length as usize * 8).saturating_sub(2)));
+            },
+        }
+    }
+    return ipv6_packet(NEXT_HEADER_ICMPV6, &upper);
+}
+
+fn generate_dhcpv6_options(rng: &mut StdRng, depth: usize) -> Vec<u8> {
+    let mut out = vec![];
+    for _ in 0 .. rng.gen_range(0 .. 4) {
+        let code = *DHCPV6_OPTION_CODES.choose(rng).unwrap();
+        let data = match code {
+            9 if depth < 3 => generate_dhcpv6_message(rng, depth + 1),
+            3 | 4 | 5 | 17 | 25 | 26 if depth < 3 => {
+                let header_size = match code {
+                    3 | 25 => 12,
+                    5 => 24,
+                    26 => 25,
+                    _ => 4,
+                };
+                let mut data = random_bytes(rng, header_size);
+                data.extend(generate_dhcpv6_options(rng, depth + 1));
+                data
+            },
+            23 => {
+                let count = rng.gen_range(0 .. 4);
+                random_bytes(rng, count * 16)
+            },
+            _ => {
+                let length = rng.gen_range(0 .. 20);
+                random_bytes(rng, length)
+            },
+        };
+        out.extend_from_slice(&code.to_be_bytes());
+
+        // Occasionally a length that doesn't match the data
+        let length = if rng.gen_bool(0.05) {
+            rng.gen()
+        } else {
+            data.len() as u16
+        };
+        out.extend_from_slice(&length.to_be_bytes());
+        out.extend(data);
+    }
+    return out;
+}
+
+fn generate_dhcpv6_message(rng: &mut StdRng, depth: usize) -> Vec<u8> {
+    let msg_type = *DHCPV6_MSG_TYPES.choose(rng).unwrap();
+    let mut out = vec![msg_type];
+    if msg_type == 12 || msg_type == 13 {
+        out.extend(random_bytes(rng, 33));
+    } else {
+        out.extend(random_bytes(rng, 3));
+    }
+    out.extend(generate_dhcpv6_options(rng, depth));
+    return out;
+}
+
+fn generate_dhcpv6(rng: &mut StdRng) -> Vec<u8> {
+    // Server to client ports, length and checksum filled in later
+    let mut upper = vec![0x02, 0x23, 0x02, 0x22, 0, 0, 0, 0];
+    upper.extend(generate_dhcpv6_message(rng, 0));
+    return ipv6_packet(NEXT_HEADER_UDP, &upper);
+}
+
+fn mutate(rng: &mut StdRng, packet: &mut Vec<u8>) {
+    for _ in 0 .. rng.gen_range(1 .. 4) {
+        if packet.is_empty() {
+            packet.push(rng.gen());
+            continue;
+        }
+        let at = rng.gen_range(0 .. packet.len());
+        match rng.gen_range(0 .. 5) {
+            0 => packet[at] ^= 1 << rng.gen_range(0 .. 8),
+            1 => packet[at] = *[0, 1, 0x7f, 0x80, 0xff].choose(rng).unwrap(),
+            2 => packet.truncate(at),
+            3 => {
+                let length = rng.gen_range(1 .. 16);
+                let inserted = random_bytes(rng, length);
+                packet.splice(at .. at, inserted);
+            },
+            _ => {
+                let end = rng.gen_range(at ..= packet.len());
+                packet.drain(at .. end);
+            },
+        }
+    }
+    if rng.gen_bool(0.7) {
+        fix_lengths(packet);
+    }
+}
+
+fn generate_config(rng: &mut StdRng) -> (ModifyConfig, Vec<Ipv6Addr>) {
+    let ips = if rng.gen_bool(0.02) {
+        // More than fit in an RDNSS option
+        (0 .. 200u16).map(|i| Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, i)).collect()
+    } else {
+        (0 .. rng.gen_range(1 .. 4)).map(|_| Ipv6Addr::from(rng.gen::<u128>())).collect()
+    };
+    let mut config = ModifyConfig {
+        mtu: if rng.gen_bool(0.5) {
+            Some(rng.gen())
+        } else {
+            None
+        },
+        pref64: if rng.gen_bool(0.2) {
+            Some(Pref64Config {
+                prefix: Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0),
+                prefix_length: *[96, 64, 32, 33].choose(rng).unwrap(),
+                lifetime: rng.gen(),
+            })
+        } else {
+            None
+        },
+        routes: (0 .. rng.gen_range(0 .. 3)).map(|_| RouteConfig {
+            prefix: Ipv6Addr::from(rng.gen::<u128>()),
+            prefix_length: rng.gen_range(0 ..= 129),
+            preference: RouterPreference::High,
+            lifetime: rng.gen(),
+        }).collect(),
+        search_domains: if rng.gen_bool(0.3) {
+            Some(SearchDomainsConfig {
+                domains: match rng.gen_range(0 .. 3) {
+                    0 => vec!["example.com".to_string()],
+                    1 => vec!["bad..name".to_string()],
+                    // More than fit in a DNSSL option
+                    _ => (0 .. 100).map(|i| format!("{}.{}.example.com", "x".repeat(20), i)).collect(),
+                },
+                lifetime: rng.gen(),
+            })
+        } else {
+            None
+        },
+        pio: if rng.gen_bool(0.3) {
+            Some(PioConfig {
+                min_valid_lifetime: rng.gen(),
+                max_valid_lifetime: rng.gen(),
+                min_preferred_lifetime: rng.gen(),
+                max_preferred_lifetime: rng.gen(),
+                on_link: rng.gen(),
+                autonomous: rng.gen(),
+                drop_prefixes: vec![(Ipv6Addr::from(rng.gen::<u128>()), rng.gen_range(0 ..= 129))],
+            })
+        } else {
+            None
+        },
+        ra_header: if rng.gen_bool(0.3) {
+            Some(RaHeaderConfig {
+                managed: rng.gen(),
+                other: rng.gen(),
+                router_lifetime: rng.gen(),
+                preference: *[None, Some(RouterPreference::High), Some(RouterPreference::Low)].choose(rng).unwrap(),
+                cur_hop_limit: rng.gen(),
+                reachable_time: rng.gen(),
+                retrans_timer: rng.gen(),
+            })
+        } else {
+            None
+        },
+        captive_portal: match rng.gen_range(0 .. 4) {
+            0 => Some("https://portalino.internal/capport".to_string()),
+            // More than fit in an RA option
+            1 => Some(format!("https://{}", "x".repeat(3000))),
+            _ => None,
+        },
+        inject_dns_lifetime: if rng.gen_bool(0.3) {
+            Some(rng.gen())
+        } else {
+            None
+        },
+    };
+    if config.ra_header.as_ref().is_some_and(|h| h.router_lifetime.is_some()) {
+        // Defaulted lifetimes copy the upstream router lifetime, which is the
+        // overridden one on a second pass
+        if let Some(pref64) = &mut config.pref64 {
+            pref64.lifetime.get_or_insert(rng.gen());
+        }
+        if let Some(search_domains) = &mut config.search_domains {
+            search_domains.lifetime.get_or_insert(rng.gen());
+        }
+        for route in &mut config.routes {
+            route.lifetime.get_or_insert(rng.gen());
+        }
+    }
+    return (config, ips);
+}
+
+fn check_output(source: &[u8], got: &[u8]) {
+    // Unchanged packets are passed through as is, even with trailing data
+    if got == source {
+        return;
+    }
+    assert!(got.len() >= IPV6_FIXED_HEADER_SIZE);
+    assert_eq!(read_u16(got, 4).unwrap() as usize, got.len() - IPV6_FIXED_HEADER_SIZE);
+    let chain = HeaderChain::parse(got).unwrap();
+    if chain.next_header == NEXT_HEADER_UDP {
+        assert_eq!(read_u16(chain.upper, 4).unwrap() as usize, chain.upper.len());
+    }
+    assert!(Ipv6Packet::parse(got).is_some());
+    assert_eq!(icmpv6_udp_checksum(got).unwrap(), [0, 0]);
+}
+
+fn check(source: &[u8], config: &ModifyConfig, ips: &[Ipv6Addr]) {
+    let res = catch_unwind(AssertUnwindSafe(|| {
+        let mut report = ModifyReport {
+            trace: Some(Default::default()),
+            ..Default::default()
+        };
+        let Ok(got) = modify(source, ips, config, &mut report) else {
+            return;
+        };
+        check_output(source, &got);
+        assert_eq!(modify(&got, ips, config, &mut ModifyReport::default()), Ok(got.clone()), "Not idempotent");
+    }));
+    if res.is_err() {
+        panic!("Failed on input {:02x?}", source);
+    }
+}
+
+fn run(seed: u64, mut generate: impl FnMut(&mut StdRng) -> Vec<u8>) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    for _ in 0 .. ITERATIONS {
+        let mut packet = generate(&mut rng);
+        let (config, ips) = generate_config(&mut rng);
+        check(&packet, &config, &ips);
+        mutate(&mut rng, &mut packet);
+        check(&packet, &config, &ips);
+    }
+}
+
+#[test]
+fn test_fuzz_arbitrary_bytes() {
+    run(1, |rng| {
+        let length = rng.gen_range(0 .. 200);
+        let mut packet = random_bytes(rng, length);
+        if rng.gen_bool(0.5) {
+            fix_lengths(&mut packet);
+        }
+        return packet;
+    });
+}
+
+#[test]
+fn test_fuzz_mutated_seeds() {
+    let seeds = seeds();
+    run(2, |rng| seeds.choose(rng).unwrap().to_vec());
+}
+
+#[test]
+fn test_fuzz_generated_ra() {
+    run(3, generate_ra);
+}
+
+#[test]
+fn test_fuzz_generated_dhcpv6() {
+    run(4, generate_dhcpv6);
+}
+
+#[test]
+fn test_routing_header_too_short() {
+    // Type 0 routing header with segments left but no room for addresses
+    let mut upper = vec![134, 0, 0, 0];
+    upper.extend_from_slice(&[0; 12]);
+    let mut packet = ipv6_packet(NEXT_HEADER_ICMPV6, &upper);
+    packet[6] = 43;
+    packet.splice(40 .. 40, [NEXT_HEADER_ICMPV6, 0, 0, 1, 0, 0, 0, 0]);
+    fix_lengths(&mut packet);
+    assert_eq!(
+        modify(&packet, &[Ipv6Addr::LOCALHOST], &ModifyConfig::default(), &mut ModifyReport::default()),
+        Err(ModifyError::Parse)
+    );
+}
+
+#[test]
+fn test_deeply_nested_dhcpv6() {
+    // IA_TAs nested far past the nesting limit
+    let mut options = vec![];
+    for _ in 0 .. 5000 {
+        let mut outer = vec![0, 4];
+        outer.extend_from_slice(&((4 + options.len()) as u16).to_be_bytes());
+        outer.extend_from_slice(&[0; 4]);
+        outer.extend(options);
+        options = outer;
+    }
+    let mut upper = vec![0x02, 0x23, 0x02, 0x22, 0, 0, 0, 0, 7, 0, 0, 0];
+    upper.extend(options);
+    let packet = ipv6_packet(NEXT_HEADER_UDP, &upper);
+    assert_eq!(
+        modify(&packet, &[Ipv6Addr::LOCALHOST], &ModifyConfig::default(), &mut ModifyReport::default()),
+        Ok(packet.clone())
+    );
+}
+
+#[test]
+fn test_dhcpv6_vendor_options() {
+    // Vendor sub-option 23 isn't DNS servers, and needn't be a list of addresses
+    let mut vendor = vec![0, 17, 0, 4 + 4 + 5, 0, 0, 0x0d, 0xe9, 0, 23, 0, 5];
+    vendor.extend_from_slice(b"vdata");
+    let mut upper = vec![0x02, 0x23, 0x02, 0x22, 0, 0, 0, 0, 7, 0, 0, 0];
+    upper.extend_from_slice(&vendor);
+    let packet = ipv6_packet(NEXT_HEADER_UDP, &upper);
+    assert_eq!(
+        modify(&packet, &[Ipv6Addr::LOCALHOST], &ModifyConfig::default(), &mut ModifyReport::default()),
+        Ok(packet.clone())
+    );
+
+    // Still passed through when DNS servers elsewhere are replaced
+    upper.extend_from_slice(&[0, 23, 0, 16]);
+    upper.extend_from_slice(&Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1).octets());
+    let packet = ipv6_packet(NEXT_HEADER_UDP, &upper);
+    let got =
+        modify(&packet, &[Ipv6Addr::LOCALHOST], &ModifyConfig::default(), &mut ModifyReport::default()).unwrap();
+    assert_ne!(got, packet);
+    assert_eq!(got[IPV6_FIXED_HEADER_SIZE + 12 .. IPV6_FIXED_HEADER_SIZE + 12 + vendor.len()], vendor[..]);
+}
+
+#[test]
+fn test_dhcpv6_too_large() {
+    // Room for the original reply but not the injected DNS option
+    let mut upper = vec![0x02, 0x23, 0x02, 0x22, 0, 0, 0, 0, 7, 0, 0, 0, 0xff, 0xfe];
+    let data_length = u16::MAX as usize - upper.len() - 2 - 16;
+    upper.extend_from_slice(&(data_length as u16).to_be_bytes());
+    upper.extend(vec![0; data_length]);
+    let packet = ipv6_packet(NEXT_HEADER_UDP, &upper);
+    assert_eq!(packet.len() - IPV6_FIXED_HEADER_SIZE, u16::MAX as usize - 16);
+    assert_eq!(
+        modify(
+            &packet,
+            &[Ipv6Addr::LOCALHOST, Ipv6Addr::UNSPECIFIED],
+            &ModifyConfig {
+                inject_dns_lifetime: Some(60),
+                ..Default::default()
+            },
+            &mut ModifyReport::default(),
+        ),
+        Err(ModifyError::Config)
+    );
+}
@@ -0,0 +1,552 @@
+use {
+    crate::manglelib::{
+        modify,
+        ModifyConfig,
+        ModifyReport,
+        PacketTrace,
+    },
+    std::net::Ipv6Addr,
+};
+
+const PAYLOAD_DHCP_NO_DNS: &[u8] = &[
+    // IPv6
+    0x6b,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x6d,
+    0x11,
+    0x01,
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x40,
+    0xff,
+    0xfe,
+    0x12,
+    0x20,
+    0x0a,
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0xb2,
+    0x6e,
+    0xbf,
+    0xff,
+    0xfe,
+    0x39,
+    0xbf,
+    0x7b,
+    // UDP
+    0x02,
+    0x23,
+    0x02,
+    0x22,
+    0x00,
+    0x6d,
+    // Checksum (zeroed)
+    0x31,
+    0xf2,
+    // DHCPv6
+    0x07,
+    0x56,
+    0x20,
+    0xfd,
+    0x00,
+    0x02,
+    0x00,
+    0x0a,
+    0x00,
+    0x03,
+    0x00,
+    0x01,
+    0x00,
+    0x19,
+    0xaa,
+    0xbc,
+    0xfa,
+    0x1b,
+    0x00,
+    0x01,
+    0x00,
+    0x0e,
+    0x00,
+    0x02,
+    0x00,
+    0x00,
+    0xab,
+    0x11,
+    0xfc,
+    0x72,
+    0xdb,
+    0x43,
+    0x6a,
+    0xc5,
+    0xca,
+    0x1f,
+    0x00,
+    0x18,
+    0x00,
+    0x19,
+    0x0a,
+    0x66,
+    0x6c,
+    0x65,
+    0x74,
+    0x73,
+    0x2d,
+    0x65,
+    0x61,
+    0x73,
+    0x74,
+    0x02,
+    0x6a,
+    0x70,
+    0x00,
+    0x05,
+    0x69,
+    0x70,
+    0x74,
+    0x76,
+    0x66,
+    0x02,
+    0x6a,
+    0x70,
+    0x00,
+    0x00,
+    0x1f,
+    0x00,
+    0x20,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x11,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x0b,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x11,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x0a,
+];
+
+#[test]
+fn test_ra_no_inject() {
+    let got = modify(
+        super::test_ra_inject_mtu::PAYLOAD_RA1,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig::default(),
+        &mut ModifyReport::default(),
+    ).unwrap();
+    assert_eq!(got, super::test_ra_inject_mtu::PAYLOAD_RA1);
+}
+
+#[test]
+fn test_ra_inject_rdnss() {
+    let mut report = ModifyReport {
+        trace: Some(PacketTrace::default()),
+        ..Default::default()
+    };
+    let got = modify(
+        super::test_ra_inject_mtu::PAYLOAD_RA1,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig {
+            inject_dns_lifetime: Some(3600),
+            ..Default::default()
+        },
+        &mut report,
+    ).unwrap();
+    let mut want = vec![
+        // ipv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x50,
+        0x3a,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // icmpv6 ra
+        0x86,
+        0x00,
+        0x8b,
+        0x06,
+        0x40,
+        0x40,
+        0x07,
+        0x08,
+        0x00,
+        0x04,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        0x01,
+        0x01,
+        0x02,
+        0x00,
+        0x40,
+        0x12,
+        0x20,
+        0x0a,
+        0x03,
+        0x04,
+        0x40,
+        0xc0,
+        0x00,
+        0x27,
+        0x8d,
+        0x00,
+        0x00,
+        0x09,
+        0x3a,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x24,
+        0x04,
+        0x7a,
+        0x82,
+        0x3c,
+        0x40,
+        0x1f,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        // rdnss option start
+        0x19,
+        0x03,
+        0x00,
+        0x00,
+        // lifetime
+        0x00,
+        0x00,
+        0x0e,
+        0x10,
+        // ip
+        0x00,
+        0x01,
+        0x00,
+        0x02,
+        0x00,
+        0x03,
+        0x00,
+        0x04,
+        0x00,
+        0x05,
+        0x00,
+        0x06,
+        0x00,
+        0x07,
+        0x00,
+        0x08,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+    assert_eq!(report, ModifyReport {
+        rdnss: true,
+        trace: Some(PacketTrace {
+            message: "RA".to_string(),
+            options_seen: vec![1, 3],
+            options_removed: vec![],
+            options_added: vec![25],
+        }),
+        ..Default::default()
+    });
+}
+
+#[test]
+fn test_dhcp_inject_dns() {
+    let mut report = ModifyReport::default();
+    let got = modify(
+        PAYLOAD_DHCP_NO_DNS,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig {
+            inject_dns_lifetime: Some(3600),
+            ..Default::default()
+        },
+        &mut report,
+    ).unwrap();
+    let mut want = vec![
+        // IPv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x81,
+        0x11,
+        0x01,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0xb2,
+        0x6e,
+        0xbf,
+        0xff,
+        0xfe,
+        0x39,
+        0xbf,
+        0x7b,
+        // UDP
+        0x02,
+        0x23,
+        0x02,
+        0x22,
+        0x00,
+        0x81,
+        // Checksum (zeroed)
+        0xe6,
+        0xc9,
+        // DHCPv6
+        0x07,
+        0x56,
+        0x20,
+        0xfd,
+        0x00,
+        0x02,
+        0x00,
+        0x0a,
+        0x00,
+        0x03,
+        0x00,
+        0x01,
+        0x00,
+        0x19,
+        0xaa,
+        0xbc,
+        0xfa,
+        0x1b,
+        0x00,
+        0x01,
+        0x00,
+        0x0e,
+        0x00,
+        0x02,
+        0x00,
+        0x00,
+        0xab,
+        0x11,
+        0xfc,
+        0x72,
+        0xdb,
+        0x43,
+        0x6a,
+        0xc5,
+        0xca,
+        0x1f,
+        0x00,
+        0x18,
+        0x00,
+        0x19,
+        0x0a,
+        0x66,
+        0x6c,
+        0x65,
+        0x74,
+        0x73,
+        0x2d,
+        0x65,
+        0x61,
+        0x73,
+        0x74,
+        0x02,
+        0x6a,
+        0x70,
+        0x00,
+        0x05,
+        0x69,
+        0x70,
+        0x74,
+        0x76,
+        0x66,
+        0x02,
+        0x6a,
+        0x70,
+        0x00,
+        0x00,
+        0x1f,
+        0x00,
+        0x20,
+        0x24,
+        0x04,
+        0x01,
+        0xa8,
+        0x11,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x0b,
+        0x24,
+        0x04,
+        0x01,
+        0xa8,
+        0x11,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x0a,
+        // DNS option
+        0x00,
+        0x17,
+        // Length
+        0x00,
+        0x10,
+        // IP
+        0x00,
+        0x01,
+        0x00,
+        0x02,
+        0x00,
+        0x03,
+        0x00,
+        0x04,
+        0x00,
+        0x05,
+        0x00,
+        0x06,
+        0x00,
+        0x07,
+        0x00,
+        0x08,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+    assert_eq!(report, ModifyReport {
+        dhcpv6_dns_servers: true,
+        ..Default::default()
+    });
+}
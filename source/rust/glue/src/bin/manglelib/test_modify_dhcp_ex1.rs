@@ -1,9 +1,13 @@
 use {
-    crate::manglelib::modify,
+    crate::manglelib::{
+        modify,
+        ModifyConfig,
+        ModifyReport,
+    },
     std::net::Ipv6Addr,
 };
 
-const PAYLOAD_DHCP1: &[u8] = &[
+pub const PAYLOAD_DHCP1: &[u8] = &[
     // IPv6
     0x6b,
     0x80,
@@ -52,9 +56,9 @@ const PAYLOAD_DHCP1: &[u8] = &[
     0x22,
     0x00,
     0x91,
-    // Checksum (zeroed)
-    0x00,
-    0x00,
+    // Checksum
+    0xe7,
+    0xfc,
     // DHCPv6
     0x07,
     0x56,
@@ -197,7 +201,12 @@ const PAYLOAD_DHCP1: &[u8] = &[
 
 #[test]
 fn test_modify_dhcp_ex1() {
-    let got = modify(PAYLOAD_DHCP1, Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8), None).unwrap();
+    let got = modify(
+        PAYLOAD_DHCP1,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig::default(),
+        &mut ModifyReport::default(),
+    ).unwrap();
     let mut want = vec![
         // IPv6
         0x6b,
@@ -0,0 +1,524 @@
+use {
+    crate::manglelib::{
+        modify,
+        ModifyConfig,
+        ModifyReport,
+    },
+    std::net::Ipv6Addr,
+};
+
+pub const PAYLOAD_DHCP_NESTED: &[u8] = &[
+    // IPv6
+    0x6b,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0xc7,
+    0x11,
+    0x01,
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x40,
+    0xff,
+    0xfe,
+    0x12,
+    0x20,
+    0x0a,
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0xb2,
+    0x6e,
+    0xbf,
+    0xff,
+    0xfe,
+    0x39,
+    0xbf,
+    0x7b,
+    // UDP
+    0x02,
+    0x23,
+    0x02,
+    0x22,
+    0x00,
+    0xc7,
+    // Checksum (zeroed)
+    0x69,
+    0xe5,
+    // DHCPv6
+    0x07,
+    0x56,
+    0x20,
+    0xfd,
+    // Server id
+    0x00,
+    0x02,
+    0x00,
+    0x0a,
+    0x00,
+    0x03,
+    0x00,
+    0x01,
+    0x00,
+    0x19,
+    0xaa,
+    0xbc,
+    0xfa,
+    0x1b,
+    // IA_NA
+    0x00,
+    0x03,
+    0x00,
+    0x4c,
+    0x00,
+    0x00,
+    0x00,
+    0x01,
+    0x00,
+    0x00,
+    0x07,
+    0x08,
+    0x00,
+    0x00,
+    0x0b,
+    0x40,
+    // IA address
+    0x00,
+    0x05,
+    0x00,
+    0x18,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x7f,
+    0x01,
+    0x00,
+    0x0b,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x12,
+    0x34,
+    0x00,
+    0x00,
+    0x0e,
+    0x10,
+    0x00,
+    0x00,
+    0x1c,
+    0x20,
+    // DNS (in IA_NA)
+    0x00,
+    0x17,
+    0x00,
+    0x20,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x7f,
+    0x01,
+    0x00,
+    0x0b,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x03,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x7f,
+    0x01,
+    0x00,
+    0x0a,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x03,
+    // IA_PD
+    0x00,
+    0x19,
+    0x00,
+    0x3d,
+    0x00,
+    0x00,
+    0x00,
+    0x02,
+    0x00,
+    0x00,
+    0x07,
+    0x08,
+    0x00,
+    0x00,
+    0x0b,
+    0x40,
+    // IA prefix
+    0x00,
+    0x1a,
+    0x00,
+    0x2d,
+    0x00,
+    0x00,
+    0x0e,
+    0x10,
+    0x00,
+    0x00,
+    0x1c,
+    0x20,
+    0x38,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x7f,
+    0x01,
+    0x0c,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    // DNS (in IA prefix)
+    0x00,
+    0x17,
+    0x00,
+    0x10,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x7f,
+    0x01,
+    0x00,
+    0x0b,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x03,
+    // Vendor options
+    0x00,
+    0x11,
+    0x00,
+    0x18,
+    0x00,
+    0x00,
+    0x0d,
+    0xe9,
+    // DNS (in vendor options)
+    0x00,
+    0x17,
+    0x00,
+    0x10,
+    0x24,
+    0x04,
+    0x01,
+    0xa8,
+    0x7f,
+    0x01,
+    0x00,
+    0x0a,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x03,
+];
+
+#[test]
+fn test_modify_dhcp_nested() {
+    let got = modify(
+        PAYLOAD_DHCP_NESTED,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig::default(),
+        &mut ModifyReport::default(),
+    ).unwrap();
+    let mut want = vec![
+        // IPv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0xb7,
+        0x11,
+        0x01,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0xb2,
+        0x6e,
+        0xbf,
+        0xff,
+        0xfe,
+        0x39,
+        0xbf,
+        0x7b,
+        // UDP
+        0x02,
+        0x23,
+        0x02,
+        0x22,
+        0x00,
+        0xb7,
+        // Checksum (zeroed)
+        0x4b,
+        0x1c,
+        // DHCPv6
+        0x07,
+        0x56,
+        0x20,
+        0xfd,
+        // Server id
+        0x00,
+        0x02,
+        0x00,
+        0x0a,
+        0x00,
+        0x03,
+        0x00,
+        0x01,
+        0x00,
+        0x19,
+        0xaa,
+        0xbc,
+        0xfa,
+        0x1b,
+        // IA_NA
+        0x00,
+        0x03,
+        0x00,
+        0x3c,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        0x00,
+        0x00,
+        0x07,
+        0x08,
+        0x00,
+        0x00,
+        0x0b,
+        0x40,
+        // IA address
+        0x00,
+        0x05,
+        0x00,
+        0x18,
+        0x24,
+        0x04,
+        0x01,
+        0xa8,
+        0x7f,
+        0x01,
+        0x00,
+        0x0b,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x12,
+        0x34,
+        0x00,
+        0x00,
+        0x0e,
+        0x10,
+        0x00,
+        0x00,
+        0x1c,
+        0x20,
+        // DNS (in IA_NA)
+        0x00,
+        0x17,
+        0x00,
+        0x10,
+        0x00,
+        0x01,
+        0x00,
+        0x02,
+        0x00,
+        0x03,
+        0x00,
+        0x04,
+        0x00,
+        0x05,
+        0x00,
+        0x06,
+        0x00,
+        0x07,
+        0x00,
+        0x08,
+        // IA_PD
+        0x00,
+        0x19,
+        0x00,
+        0x3d,
+        0x00,
+        0x00,
+        0x00,
+        0x02,
+        0x00,
+        0x00,
+        0x07,
+        0x08,
+        0x00,
+        0x00,
+        0x0b,
+        0x40,
+        // IA prefix
+        0x00,
+        0x1a,
+        0x00,
+        0x2d,
+        0x00,
+        0x00,
+        0x0e,
+        0x10,
+        0x00,
+        0x00,
+        0x1c,
+        0x20,
+        0x38,
+        0x24,
+        0x04,
+        0x01,
+        0xa8,
+        0x7f,
+        0x01,
+        0x0c,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        // DNS (in IA prefix)
+        0x00,
+        0x17,
+        0x00,
+        0x10,
+        0x00,
+        0x01,
+        0x00,
+        0x02,
+        0x00,
+        0x03,
+        0x00,
+        0x04,
+        0x00,
+        0x05,
+        0x00,
+        0x06,
+        0x00,
+        0x07,
+        0x00,
+        0x08,
+        // Vendor options, passed through since sub-option codes are vendor specific
+        0x00,
+        0x11,
+        0x00,
+        0x18,
+        0x00,
+        0x00,
+        0x0d,
+        0xe9,
+        // Vendor sub-option 23 (not DNS)
+        0x00,
+        0x17,
+        0x00,
+        0x10,
+        0x24,
+        0x04,
+        0x01,
+        0xa8,
+        0x7f,
+        0x01,
+        0x00,
+        0x0a,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x03,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
@@ -0,0 +1,512 @@
+use {
+    crate::manglelib::{
+        icmpv6_udp_checksum,
+        ipv6::{
+            Icmpv6Body,
+            Ipv6Packet,
+            Ipv6Payload,
+        },
+        modify,
+        ra::{
+            RaOption,
+            RouterPreference,
+            OPT_DNSSL,
+        },
+        ModifyConfig,
+        ModifyReport,
+        Pref64Config,
+        RaHeaderConfig,
+        RouteConfig,
+        SearchDomainsConfig,
+        INJECTED_OPTION_LIFETIME_DEFAULT,
+    },
+    std::net::Ipv6Addr,
+};
+
+#[test]
+fn test_ra_header_override() {
+    let got = modify(
+        super::test_ra_inject_mtu::PAYLOAD_RA1,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig {
+            ra_header: Some(RaHeaderConfig {
+                router_lifetime: Some(600),
+                preference: Some(RouterPreference::High),
+                cur_hop_limit: Some(128),
+                reachable_time: Some(30000),
+                retrans_timer: Some(1000),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        &mut ModifyReport::default(),
+    ).unwrap();
+    let mut want = vec![
+        // ipv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x38,
+        0x3a,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // icmpv6 ra
+        0x86,
+        0x00,
+        0xb8,
+        0xd9,
+        0x80,
+        0x48,
+        0x02,
+        0x58,
+        0x00,
+        0x00,
+        0x75,
+        0x30,
+        0x00,
+        0x00,
+        0x03,
+        0xe8,
+        0x01,
+        0x01,
+        0x02,
+        0x00,
+        0x40,
+        0x12,
+        0x20,
+        0x0a,
+        0x03,
+        0x04,
+        0x40,
+        0xc0,
+        0x00,
+        0x27,
+        0x8d,
+        0x00,
+        0x00,
+        0x09,
+        0x3a,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x24,
+        0x04,
+        0x7a,
+        0x82,
+        0x3c,
+        0x40,
+        0x1f,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_ra_header_not_default_router() {
+    // Preference is cleared, PREF64 lifetime isn't tied to the router lifetime
+    let got = modify(
+        super::test_ra_inject_mtu::PAYLOAD_RA1,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig {
+            pref64: Some(Pref64Config {
+                prefix: Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0),
+                prefix_length: 96,
+                lifetime: None,
+            }),
+            ra_header: Some(RaHeaderConfig {
+                router_lifetime: Some(0),
+                preference: Some(RouterPreference::High),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        &mut ModifyReport::default(),
+    ).unwrap();
+    let mut want = vec![
+        // ipv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x48,
+        0x3a,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // icmpv6 ra
+        0x86,
+        0x00,
+        0x8c,
+        0x43,
+        0x40,
+        0x40,
+        0x00,
+        0x00,
+        0x00,
+        0x04,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        0x01,
+        0x01,
+        0x02,
+        0x00,
+        0x40,
+        0x12,
+        0x20,
+        0x0a,
+        0x03,
+        0x04,
+        0x40,
+        0xc0,
+        0x00,
+        0x27,
+        0x8d,
+        0x00,
+        0x00,
+        0x09,
+        0x3a,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x24,
+        0x04,
+        0x7a,
+        0x82,
+        0x3c,
+        0x40,
+        0x1f,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        // pref64
+        0x26,
+        0x02,
+        0x07,
+        0x08,
+        0x00,
+        0x64,
+        0xff,
+        0x9b,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_ra_flags() {
+    // No options change, only the flags
+    let got = modify(
+        super::test_ra_inject_mtu::PAYLOAD_RA1,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig {
+            ra_header: Some(RaHeaderConfig {
+                managed: Some(true),
+                other: Some(false),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        &mut ModifyReport::default(),
+    ).unwrap();
+    let mut want = vec![
+        // ipv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x38,
+        0x3a,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // icmpv6 ra
+        0x86,
+        0x00,
+        0xb2,
+        0x15,
+        0x40,
+        0x80,
+        0x07,
+        0x08,
+        0x00,
+        0x04,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        0x01,
+        0x01,
+        0x02,
+        0x00,
+        0x40,
+        0x12,
+        0x20,
+        0x0a,
+        0x03,
+        0x04,
+        0x40,
+        0xc0,
+        0x00,
+        0x27,
+        0x8d,
+        0x00,
+        0x00,
+        0x09,
+        0x3a,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x24,
+        0x04,
+        0x7a,
+        0x82,
+        0x3c,
+        0x40,
+        0x1f,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_ra_flags_already_set() {
+    let got = modify(
+        super::test_ra_inject_mtu::PAYLOAD_RA1,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig {
+            ra_header: Some(RaHeaderConfig {
+                other: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        &mut ModifyReport::default(),
+    ).unwrap();
+    assert_eq!(got, super::test_ra_inject_mtu::PAYLOAD_RA1);
+}
+
+#[test]
+fn test_upstream_not_default_router() {
+    // Upstream router lifetime 0, injected options still get a nonzero lifetime
+    let mut source = super::test_ra_inject_mtu::PAYLOAD_RA1.to_vec();
+    source[46 .. 48].copy_from_slice(&[0, 0]);
+    source[42 .. 44].copy_from_slice(&[0, 0]);
+    let checksum = icmpv6_udp_checksum(&source).unwrap();
+    source[42 .. 44].copy_from_slice(&checksum);
+    let got = modify(&source, &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)], &ModifyConfig {
+        pref64: Some(Pref64Config {
+            prefix: Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0),
+            prefix_length: 96,
+            lifetime: None,
+        }),
+        routes: vec![RouteConfig {
+            prefix: Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0),
+            prefix_length: 96,
+            preference: RouterPreference::Medium,
+            lifetime: None,
+        }],
+        search_domains: Some(SearchDomainsConfig {
+            domains: vec!["portalino.internal".to_string()],
+            lifetime: None,
+        }),
+        ..Default::default()
+    }, &mut ModifyReport::default()).unwrap();
+    let Ipv6Payload::Icmpv6(icmpv6) = Ipv6Packet::parse(&got).unwrap().payload else {
+        panic!();
+    };
+    let Icmpv6Body::RouterAdvertisement(ra) = icmpv6.body else {
+        panic!();
+    };
+    assert_eq!(ra.router_lifetime, 0);
+    let mut found = 0;
+    for option in &ra.options {
+        match option {
+            RaOption::Pref64 { lifetime, .. } => {
+                assert_eq!(*lifetime as u32, INJECTED_OPTION_LIFETIME_DEFAULT);
+                found += 1;
+            },
+            RaOption::RouteInformation { lifetime, .. } => {
+                assert_eq!(*lifetime, INJECTED_OPTION_LIFETIME_DEFAULT);
+                found += 1;
+            },
+            RaOption::Unknown { type_: OPT_DNSSL, body } => {
+                assert_eq!(body[2 .. 6], INJECTED_OPTION_LIFETIME_DEFAULT.to_be_bytes());
+                found += 1;
+            },
+            _ => { },
+        }
+    }
+    assert_eq!(found, 3);
+}
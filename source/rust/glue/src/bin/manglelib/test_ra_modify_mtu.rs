@@ -1,9 +1,13 @@
 use {
-    crate::manglelib::modify,
+    crate::manglelib::{
+        modify,
+        ModifyConfig,
+        ModifyReport,
+    },
     std::net::Ipv6Addr,
 };
 
-const PAYLOAD_RA1: &[u8] = &[
+pub const PAYLOAD_RA1: &[u8] = &[
     // ipv6
     0x6b,
     0x80,
@@ -84,7 +88,15 @@ const PAYLOAD_RA1: &[u8] = &[
 
 #[test]
 fn test() {
-    let got = modify(PAYLOAD_RA1, Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8), Some(13)).unwrap();
+    let got = modify(
+        PAYLOAD_RA1,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig {
+            mtu: Some(13),
+            ..Default::default()
+        },
+        &mut ModifyReport::default(),
+    ).unwrap();
     let mut want = vec![
         // ipv6
         0x6b,
@@ -0,0 +1,395 @@
+use {
+    crate::manglelib::{
+        clamp_lifetime,
+        modify,
+        prefix_within,
+        ModifyConfig,
+        ModifyReport,
+        PioConfig,
+    },
+    std::net::Ipv6Addr,
+};
+
+#[test]
+fn test_clamp_lifetime() {
+    assert_eq!(clamp_lifetime(100, Some(200), None), 200);
+    assert_eq!(clamp_lifetime(300, None, Some(200)), 200);
+    assert_eq!(clamp_lifetime(0xffffffff, Some(60), Some(86400)), 86400);
+
+    // Withdrawn, not raised
+    assert_eq!(clamp_lifetime(0, Some(200), Some(300)), 0);
+}
+
+#[test]
+fn test_prefix_within() {
+    let prefix = "2404:7a82:3c40:1f00::".parse().unwrap();
+    assert!(prefix_within(prefix, 64, "2404:7a82::".parse().unwrap(), 32));
+    assert!(prefix_within(prefix, 64, prefix, 64));
+    assert!(prefix_within(prefix, 64, Ipv6Addr::UNSPECIFIED, 0));
+    assert!(!prefix_within(prefix, 64, "2404:7a83::".parse().unwrap(), 32));
+    assert!(!prefix_within(prefix, 64, prefix, 96));
+}
+
+#[test]
+fn test_pio_clamp_max() {
+    let got = modify(
+        super::test_ra_inject_mtu::PAYLOAD_RA1,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig {
+            pio: Some(PioConfig {
+                max_valid_lifetime: Some(86400),
+                max_preferred_lifetime: Some(14400),
+                autonomous: Some(false),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        &mut ModifyReport::default(),
+    ).unwrap();
+    let mut want = vec![
+        // ipv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x38,
+        0x3a,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // icmpv6 ra
+        0x86,
+        0x00,
+        0xf0,
+        0x84,
+        0x40,
+        0x40,
+        0x07,
+        0x08,
+        0x00,
+        0x04,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        0x01,
+        0x01,
+        0x02,
+        0x00,
+        0x40,
+        0x12,
+        0x20,
+        0x0a,
+        // Prefix information
+        0x03,
+        0x04,
+        0x40,
+        0x80,
+        // Valid lifetime
+        0x00,
+        0x01,
+        0x51,
+        0x80,
+        // Preferred lifetime
+        0x00,
+        0x00,
+        0x38,
+        0x40,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x24,
+        0x04,
+        0x7a,
+        0x82,
+        0x3c,
+        0x40,
+        0x1f,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_pio_raise_min() {
+    let got = modify(
+        super::test_ra_inject_mtu::PAYLOAD_RA1,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig {
+            pio: Some(PioConfig {
+                min_valid_lifetime: Some(5184000),
+                min_preferred_lifetime: Some(1209600),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        &mut ModifyReport::default(),
+    ).unwrap();
+    let mut want = vec![
+        // ipv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x38,
+        0x3a,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // icmpv6 ra
+        0x86,
+        0x00,
+        0xea,
+        0xa4,
+        0x40,
+        0x40,
+        0x07,
+        0x08,
+        0x00,
+        0x04,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        0x01,
+        0x01,
+        0x02,
+        0x00,
+        0x40,
+        0x12,
+        0x20,
+        0x0a,
+        // Prefix information
+        0x03,
+        0x04,
+        0x40,
+        0xc0,
+        // Valid lifetime
+        0x00,
+        0x4f,
+        0x1a,
+        0x00,
+        // Preferred lifetime
+        0x00,
+        0x12,
+        0x75,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x24,
+        0x04,
+        0x7a,
+        0x82,
+        0x3c,
+        0x40,
+        0x1f,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_pio_drop() {
+    let got = modify(
+        super::test_ra_inject_mtu::PAYLOAD_RA1,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig {
+            pio: Some(PioConfig {
+                drop_prefixes: vec![("2404:7a82::".parse().unwrap(), 32)],
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        &mut ModifyReport::default(),
+    ).unwrap();
+    let mut want = vec![
+        // ipv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x18,
+        0x3a,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // icmpv6 ra
+        0x86,
+        0x00,
+        0xb7,
+        0xb1,
+        0x40,
+        0x40,
+        0x07,
+        0x08,
+        0x00,
+        0x04,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        0x01,
+        0x01,
+        0x02,
+        0x00,
+        0x40,
+        0x12,
+        0x20,
+        0x0a,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
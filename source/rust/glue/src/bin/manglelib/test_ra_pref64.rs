@@ -0,0 +1,444 @@
+use {
+    crate::manglelib::{
+        modify,
+        ra::{
+            pref64_option,
+            RaOption,
+        },
+        ModifyConfig,
+        ModifyReport,
+        Pref64Config,
+    },
+    std::net::Ipv6Addr,
+};
+
+pub const PAYLOAD_RA_PREF64: &[u8] = &[
+    // ipv6
+    0x6b,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x48,
+    0x3a,
+    0xff,
+    0xfe,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x40,
+    0xff,
+    0xfe,
+    0x12,
+    0x20,
+    0x0a,
+    0xff,
+    0x02,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x01,
+    // icmpv6 ra
+    0x86,
+    0x00,
+    0x5b,
+    0x69,
+    0x40,
+    0x40,
+    0x07,
+    0x08,
+    0x00,
+    0x04,
+    0x93,
+    0xe0,
+    0x00,
+    0x00,
+    0x27,
+    0x10,
+    0x01,
+    0x01,
+    0x02,
+    0x00,
+    0x40,
+    0x12,
+    0x20,
+    0x0a,
+    0x03,
+    0x04,
+    0x40,
+    0xc0,
+    0x00,
+    0x27,
+    0x8d,
+    0x00,
+    0x00,
+    0x09,
+    0x3a,
+    0x80,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x24,
+    0x04,
+    0x7a,
+    0x82,
+    0x3c,
+    0x40,
+    0x1f,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    // pref64 option start
+    0x26,
+    0x02,
+    // scaled lifetime + plc
+    0x03,
+    0x21,
+    // prefix
+    0x20,
+    0x01,
+    0x0d,
+    0xb8,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+    0x00,
+];
+
+fn config(lifetime: Option<u32>) -> ModifyConfig {
+    return ModifyConfig {
+        pref64: Some(Pref64Config {
+            prefix: Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0),
+            prefix_length: 96,
+            lifetime: lifetime,
+        }),
+        ..Default::default()
+    };
+}
+
+#[test]
+fn test_inject() {
+    let got = modify(
+        super::test_ra_inject_mtu::PAYLOAD_RA1,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &config(Some(1800)),
+        &mut ModifyReport::default(),
+    ).unwrap();
+    let mut want = vec![
+        // ipv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x48,
+        0x3a,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // icmpv6 ra
+        0x86,
+        0x00,
+        0x85,
+        0x3b,
+        0x40,
+        0x40,
+        0x07,
+        0x08,
+        0x00,
+        0x04,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        0x01,
+        0x01,
+        0x02,
+        0x00,
+        0x40,
+        0x12,
+        0x20,
+        0x0a,
+        0x03,
+        0x04,
+        0x40,
+        0xc0,
+        0x00,
+        0x27,
+        0x8d,
+        0x00,
+        0x00,
+        0x09,
+        0x3a,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x24,
+        0x04,
+        0x7a,
+        0x82,
+        0x3c,
+        0x40,
+        0x1f,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        // pref64 option start
+        0x26,
+        0x02,
+        // scaled lifetime + plc
+        0x07,
+        0x08,
+        // prefix
+        0x00,
+        0x64,
+        0xff,
+        0x9b,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_replace() {
+    let got = modify(
+        PAYLOAD_RA_PREF64,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &config(None),
+        &mut ModifyReport::default(),
+    ).unwrap();
+    let mut want = vec![
+        // ipv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x48,
+        0x3a,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // icmpv6 ra
+        0x86,
+        0x00,
+        0x85,
+        0x3b,
+        0x40,
+        0x40,
+        0x07,
+        0x08,
+        0x00,
+        0x04,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        0x01,
+        0x01,
+        0x02,
+        0x00,
+        0x40,
+        0x12,
+        0x20,
+        0x0a,
+        0x03,
+        0x04,
+        0x40,
+        0xc0,
+        0x00,
+        0x27,
+        0x8d,
+        0x00,
+        0x00,
+        0x09,
+        0x3a,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x24,
+        0x04,
+        0x7a,
+        0x82,
+        0x3c,
+        0x40,
+        0x1f,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        // pref64 option start
+        0x26,
+        0x02,
+        // scaled lifetime + plc
+        0x07,
+        0x08,
+        // prefix
+        0x00,
+        0x64,
+        0xff,
+        0x9b,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_lifetime_plc() {
+    let RaOption::Pref64 { lifetime, prefix, prefix_length } =
+        pref64_option("2001:db8:1234:5678::".parse().unwrap(), 32, 1801).unwrap() else {
+            panic!();
+        };
+    assert_eq!(lifetime, 1808);
+    assert_eq!(prefix, "2001:db8::".parse::<Ipv6Addr>().unwrap());
+    assert_eq!(prefix_length, 32);
+    let RaOption::Pref64 { lifetime, .. } = pref64_option(Ipv6Addr::UNSPECIFIED, 96, 100000).unwrap() else {
+        panic!();
+    };
+    assert_eq!(lifetime, 65528);
+    assert!(pref64_option(Ipv6Addr::UNSPECIFIED, 80, 600).is_none());
+}
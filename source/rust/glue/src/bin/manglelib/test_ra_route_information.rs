@@ -0,0 +1,388 @@
+use {
+    crate::manglelib::{
+        icmpv6_udp_checksum,
+        ipv6::{
+            Icmpv6Body,
+            Ipv6Packet,
+            Ipv6Payload,
+        },
+        modify,
+        ra::{
+            RouterPreference,
+            OPT_ROUTE_INFORMATION,
+        },
+        ModifyConfig,
+        ModifyReport,
+        RouteConfig,
+    },
+    std::net::Ipv6Addr,
+};
+
+fn nat64_config() -> ModifyConfig {
+    return ModifyConfig {
+        routes: vec![RouteConfig {
+            prefix: Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0),
+            prefix_length: 96,
+            preference: RouterPreference::High,
+            lifetime: None,
+        }],
+        ..Default::default()
+    };
+}
+
+#[test]
+fn test_route_information_nat64() {
+    let got = modify(
+        super::test_ra_inject_mtu::PAYLOAD_RA1,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &nat64_config(),
+        &mut ModifyReport::default(),
+    ).unwrap();
+    let mut want = vec![
+        // ipv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x50,
+        0x3a,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // icmpv6 ra
+        0x86,
+        0x00,
+        0x33,
+        0x2a,
+        0x40,
+        0x40,
+        0x07,
+        0x08,
+        0x00,
+        0x04,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        0x01,
+        0x01,
+        0x02,
+        0x00,
+        0x40,
+        0x12,
+        0x20,
+        0x0a,
+        0x03,
+        0x04,
+        0x40,
+        0xc0,
+        0x00,
+        0x27,
+        0x8d,
+        0x00,
+        0x00,
+        0x09,
+        0x3a,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x24,
+        0x04,
+        0x7a,
+        0x82,
+        0x3c,
+        0x40,
+        0x1f,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        // route information
+        0x18,
+        0x03,
+        0x60,
+        0x08,
+        0x00,
+        0x00,
+        0x07,
+        0x08,
+        0x00,
+        0x64,
+        0xff,
+        0x9b,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_route_information_lengths() {
+    let got = modify(
+        super::test_ra_inject_mtu::PAYLOAD_RA1,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig {
+            routes: vec![
+                RouteConfig {
+                    prefix: Ipv6Addr::UNSPECIFIED,
+                    prefix_length: 0,
+                    preference: RouterPreference::Low,
+                    lifetime: Some(3600),
+                },
+                RouteConfig {
+                    // Host bits are cleared
+                    prefix: Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1),
+                    prefix_length: 48,
+                    preference: RouterPreference::Medium,
+                    lifetime: Some(3600),
+                },
+            ],
+            ..Default::default()
+        },
+        &mut ModifyReport::default(),
+    ).unwrap();
+    let mut want = vec![
+        // ipv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x50,
+        0x3a,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // icmpv6 ra
+        0x86,
+        0x00,
+        0x08,
+        0x48,
+        0x40,
+        0x40,
+        0x07,
+        0x08,
+        0x00,
+        0x04,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        0x01,
+        0x01,
+        0x02,
+        0x00,
+        0x40,
+        0x12,
+        0x20,
+        0x0a,
+        0x03,
+        0x04,
+        0x40,
+        0xc0,
+        0x00,
+        0x27,
+        0x8d,
+        0x00,
+        0x00,
+        0x09,
+        0x3a,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x24,
+        0x04,
+        0x7a,
+        0x82,
+        0x3c,
+        0x40,
+        0x1f,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        // route information
+        0x18,
+        0x01,
+        0x00,
+        0x18,
+        0x00,
+        0x00,
+        0x0e,
+        0x10,
+        // route information
+        0x18,
+        0x02,
+        0x30,
+        0x00,
+        0x00,
+        0x00,
+        0x0e,
+        0x10,
+        0x20,
+        0x01,
+        0x0d,
+        0xb8,
+        0x00,
+        0x01,
+        0x00,
+        0x00,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_route_information_replaced() {
+    let config = nat64_config();
+    let once = modify(
+        super::test_ra_inject_mtu::PAYLOAD_RA1,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &config,
+        &mut ModifyReport::default(),
+    ).unwrap();
+    let twice = modify(&once, &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)], &config, &mut ModifyReport::default()).unwrap();
+    assert_eq!(twice, once);
+}
+
+#[test]
+fn test_route_information_replaced_non_minimal() {
+    // Upstream RIO for the NAT64 prefix, with 8 bytes of padding after the prefix so
+    // it isn't parsed
+    let mut source = super::test_ra_inject_mtu::PAYLOAD_RA1.to_vec();
+    source.extend_from_slice(&[OPT_ROUTE_INFORMATION, 4, 96, 0x08, 0x00, 0x00, 0x0e, 0x10]);
+    source.extend_from_slice(&Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0).octets());
+    source.extend_from_slice(&[0; 8]);
+    let payload_length = (source.len() - 40) as u16;
+    source[4 .. 6].copy_from_slice(&payload_length.to_be_bytes());
+    source[42 .. 44].copy_from_slice(&[0, 0]);
+    let checksum = icmpv6_udp_checksum(&source).unwrap();
+    source[42 .. 44].copy_from_slice(&checksum);
+    let got =
+        modify(
+            &source,
+            &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+            &nat64_config(),
+            &mut ModifyReport::default(),
+        ).unwrap();
+    let Ipv6Payload::Icmpv6(icmpv6) = Ipv6Packet::parse(&got).unwrap().payload else {
+        panic!();
+    };
+    let Icmpv6Body::RouterAdvertisement(ra) = icmpv6.body else {
+        panic!();
+    };
+    let routes = ra.options.iter().filter_map(|option| option.route_prefix()).collect::<Vec<_>>();
+    assert_eq!(routes, vec![(Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0), 96)]);
+}
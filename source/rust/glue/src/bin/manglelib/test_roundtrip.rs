@@ -0,0 +1,23 @@
+use {
+    crate::manglelib::ipv6::Ipv6Packet,
+};
+
+fn check_roundtrip(source: &[u8]) {
+    let got = Ipv6Packet::parse(source).unwrap().serialize().unwrap();
+    assert_eq!(got, source);
+}
+
+#[test]
+fn test_roundtrip_ra_mtu() {
+    check_roundtrip(super::test_ra_modify_mtu::PAYLOAD_RA1);
+}
+
+#[test]
+fn test_roundtrip_ra_prefix() {
+    check_roundtrip(super::test_ra_inject_mtu::PAYLOAD_RA1);
+}
+
+#[test]
+fn test_roundtrip_dhcp() {
+    check_roundtrip(super::test_modify_dhcp_ex1::PAYLOAD_DHCP1);
+}
@@ -0,0 +1,226 @@
+use {
+    crate::manglelib::{
+        icmpv6_udp_checksum,
+        ra_router_lifetime,
+        unsolicited_ra,
+        ModifyConfig,
+        ModifyReport,
+    },
+    std::net::Ipv6Addr,
+};
+
+const OLD: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+const NEW: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2);
+
+fn config() -> ModifyConfig {
+    return ModifyConfig {
+        inject_dns_lifetime: Some(600),
+        ..Default::default()
+    };
+}
+
+#[test]
+fn test_unsolicited_ra() {
+    let got = unsolicited_ra(
+        super::test_ra_inject_mtu::PAYLOAD_RA1,
+        &[NEW],
+        &[OLD, NEW],
+        &config(),
+        &mut ModifyReport::default(),
+    ).unwrap();
+    let mut want = vec![
+        // ipv6
+        0x6b,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x68,
+        0x3a,
+        0xff,
+        0xfe,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x40,
+        0xff,
+        0xfe,
+        0x12,
+        0x20,
+        0x0a,
+        0xff,
+        0x02,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+        // icmpv6 ra
+        0x86,
+        0x00,
+        0x22,
+        0x52,
+        0x40,
+        0x40,
+        0x07,
+        0x08,
+        0x00,
+        0x04,
+        0x93,
+        0xe0,
+        0x00,
+        0x00,
+        0x27,
+        0x10,
+        0x01,
+        0x01,
+        0x02,
+        0x00,
+        0x40,
+        0x12,
+        0x20,
+        0x0a,
+        0x03,
+        0x04,
+        0x40,
+        0xc0,
+        0x00,
+        0x27,
+        0x8d,
+        0x00,
+        0x00,
+        0x09,
+        0x3a,
+        0x80,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x24,
+        0x04,
+        0x7a,
+        0x82,
+        0x3c,
+        0x40,
+        0x1f,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        // rdnss
+        0x19,
+        0x03,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x02,
+        0x58,
+        0x20,
+        0x01,
+        0x0d,
+        0xb8,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x02,
+        // rdnss, withdrawn
+        0x19,
+        0x03,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x20,
+        0x01,
+        0x0d,
+        0xb8,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+        0x01,
+    ];
+    if want.len() < got.len() {
+        want.resize(got.len(), 0);
+    }
+    for (i, (got, want)) in Iterator::zip(got.iter(), want.iter()).enumerate() {
+        let got = *got;
+        let want = *want;
+        println!("{:03}: {:x} {} {:x}", i, got, if got == want {
+            "=="
+        } else {
+            "!="
+        }, want);
+    }
+    assert_eq!(got, want);
+}
+
+#[test]
+fn test_unsolicited_ra_unicast() {
+    // Solicited RA, unicast to a client
+    let mut source = super::test_ra_inject_mtu::PAYLOAD_RA1.to_vec();
+    source[24 .. 40].copy_from_slice(&Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0x1234).octets());
+    source[42 .. 44].copy_from_slice(&[0, 0]);
+    let checksum = icmpv6_udp_checksum(&source).unwrap();
+    source[42 .. 44].copy_from_slice(&checksum);
+    assert_eq!(
+        unsolicited_ra(&source, &[NEW], &[OLD], &config(), &mut ModifyReport::default()).unwrap(),
+        unsolicited_ra(super::test_ra_inject_mtu::PAYLOAD_RA1, &[NEW], &[OLD], &config(), &mut ModifyReport::default())
+            .unwrap()
+    );
+}
+
+#[test]
+fn test_unsolicited_ra_no_rdnss() {
+    // Nothing to withdraw if the RA doesn't advertise resolvers
+    let mut report = ModifyReport::default();
+    assert_eq!(
+        unsolicited_ra(super::test_ra_inject_mtu::PAYLOAD_RA1, &[NEW], &[OLD], &ModifyConfig::default(), &mut report),
+        Ok(super::test_ra_inject_mtu::PAYLOAD_RA1.to_vec())
+    );
+    assert!(!report.rdnss);
+}
+
+#[test]
+fn test_ra_router_lifetime() {
+    assert_eq!(ra_router_lifetime(super::test_ra_inject_mtu::PAYLOAD_RA1), Some(1800));
+    assert_eq!(ra_router_lifetime(super::test_modify_dhcp_ex1::PAYLOAD_DHCP1), None);
+}
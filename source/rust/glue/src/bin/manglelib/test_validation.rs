@@ -0,0 +1,83 @@
+use {
+    crate::manglelib::{
+        icmpv6_udp_checksum,
+        modify,
+        ModifyConfig,
+        ModifyError,
+        ModifyReport,
+    },
+    std::net::Ipv6Addr,
+};
+
+// Checksum offsets in the test payloads, which have no extension headers
+const ICMPV6_CHECKSUM_START: usize = 42;
+const UDP_CHECKSUM_START: usize = 46;
+
+fn refresh_checksum(packet: &mut [u8], checksum_start: usize) {
+    packet[checksum_start .. checksum_start + 2].copy_from_slice(&[0, 0]);
+    let checksum = icmpv6_udp_checksum(packet).unwrap();
+    packet[checksum_start .. checksum_start + 2].copy_from_slice(&checksum);
+}
+
+fn check(packet: &[u8]) -> Result<Vec<u8>, ModifyError> {
+    return modify(
+        packet,
+        &[Ipv6Addr::new(1, 2, 3, 4, 5, 6, 7, 8)],
+        &ModifyConfig {
+            mtu: Some(1280),
+            ..Default::default()
+        },
+        &mut ModifyReport::default(),
+    );
+}
+
+#[test]
+fn test_valid() {
+    assert!(check(super::test_ra_inject_mtu::PAYLOAD_RA1).is_ok());
+    assert!(check(super::test_modify_dhcp_ex1::PAYLOAD_DHCP1).is_ok());
+}
+
+#[test]
+fn test_ra_bad_checksum() {
+    let mut packet = super::test_ra_inject_mtu::PAYLOAD_RA1.to_vec();
+    packet[ICMPV6_CHECKSUM_START] ^= 0x01;
+    assert_eq!(check(&packet), Err(ModifyError::Invalid));
+}
+
+#[test]
+fn test_ra_forwarded() {
+    // Hop limit isn't in the pseudo header, the checksum is still good
+    let mut packet = super::test_ra_inject_mtu::PAYLOAD_RA1.to_vec();
+    packet[7] = 254;
+    assert_eq!(check(&packet), Err(ModifyError::Invalid));
+}
+
+#[test]
+fn test_ra_global_source() {
+    let mut packet = super::test_ra_inject_mtu::PAYLOAD_RA1.to_vec();
+    packet[8 .. 24].copy_from_slice(&Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1).octets());
+    refresh_checksum(&mut packet, ICMPV6_CHECKSUM_START);
+    assert_eq!(check(&packet), Err(ModifyError::Invalid));
+}
+
+#[test]
+fn test_ra_nonzero_code() {
+    let mut packet = super::test_ra_inject_mtu::PAYLOAD_RA1.to_vec();
+    packet[41] = 1;
+    refresh_checksum(&mut packet, ICMPV6_CHECKSUM_START);
+    assert_eq!(check(&packet), Err(ModifyError::Invalid));
+}
+
+#[test]
+fn test_dhcp_bad_checksum() {
+    let mut packet = super::test_modify_dhcp_ex1::PAYLOAD_DHCP1.to_vec();
+    packet[UDP_CHECKSUM_START + 1] ^= 0x01;
+    assert_eq!(check(&packet), Err(ModifyError::Invalid));
+}
+
+#[test]
+fn test_dhcp_no_checksum() {
+    let mut packet = super::test_modify_dhcp_ex1::PAYLOAD_DHCP1.to_vec();
+    packet[UDP_CHECKSUM_START .. UDP_CHECKSUM_START + 2].copy_from_slice(&[0, 0]);
+    assert_eq!(check(&packet), Err(ModifyError::Invalid));
+}
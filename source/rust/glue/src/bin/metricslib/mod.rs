@@ -0,0 +1,351 @@
+use {
+    crate::{
+        guardlib::{
+            format_mac,
+            RogueSource,
+            RouterId,
+        },
+        manglelib::ModifyReport,
+    },
+    loga::{
+        ea,
+        ErrContext,
+        Log,
+        ResultContext,
+    },
+    std::{
+        fmt::Write as _,
+        io::{
+            Read,
+            Write,
+        },
+        net::{
+            Ipv6Addr,
+            SocketAddr,
+            TcpListener,
+            TcpStream,
+        },
+        sync::{
+            Arc,
+            Mutex,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+#[cfg(test)]
+mod test_render;
+
+const PREFIX: &str = "mangle_ip_configure";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const REQUEST_MAX_SIZE: usize = 8192;
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PolicyCounts {
+    pub dropped: u64,
+    pub accepted: u64,
+}
+
+impl PolicyCounts {
+    fn add(&mut self, other: &PolicyCounts) {
+        self.dropped += other.dropped;
+        self.accepted += other.accepted;
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Counters {
+    pub received: u64,
+    pub rewritten: u64,
+    /// Modified successfully but nothing needed changing
+    pub unchanged: u64,
+    pub no_ip: PolicyCounts,
+    pub parse_error: PolicyCounts,
+    /// Bad checksum or failed RA validation
+    pub invalid: PolicyCounts,
+    /// RA guard, from an untrusted router
+    pub rogue_router: PolicyCounts,
+    pub not_ra: PolicyCounts,
+    pub not_dhcpv6: PolicyCounts,
+    pub config_error: PolicyCounts,
+    pub injected_mtu: u64,
+    pub injected_rdnss: u64,
+    pub injected_pref64: u64,
+    pub injected_route_information: u64,
+    pub injected_dnssl: u64,
+    pub injected_captive_portal: u64,
+    pub injected_dhcpv6_dns_servers: u64,
+    pub injected_dhcpv6_domain_list: u64,
+    pub injected_dhcpv6_captive_portal: u64,
+}
+
+impl Counters {
+    pub fn add(&mut self, other: &Counters) {
+        self.received += other.received;
+        self.rewritten += other.rewritten;
+        self.unchanged += other.unchanged;
+        self.no_ip.add(&other.no_ip);
+        self.parse_error.add(&other.parse_error);
+        self.invalid.add(&other.invalid);
+        self.rogue_router.add(&other.rogue_router);
+        self.not_ra.add(&other.not_ra);
+        self.not_dhcpv6.add(&other.not_dhcpv6);
+        self.config_error.add(&other.config_error);
+        self.injected_mtu += other.injected_mtu;
+        self.injected_rdnss += other.injected_rdnss;
+        self.injected_pref64 += other.injected_pref64;
+        self.injected_route_information += other.injected_route_information;
+        self.injected_dnssl += other.injected_dnssl;
+        self.injected_captive_portal += other.injected_captive_portal;
+        self.injected_dhcpv6_dns_servers += other.injected_dhcpv6_dns_servers;
+        self.injected_dhcpv6_domain_list += other.injected_dhcpv6_domain_list;
+        self.injected_dhcpv6_captive_portal += other.injected_dhcpv6_captive_portal;
+    }
+
+    pub fn add_report(&mut self, report: &ModifyReport) {
+        self.injected_mtu += report.mtu as u64;
+        self.injected_rdnss += report.rdnss as u64;
+        self.injected_pref64 += report.pref64 as u64;
+        self.injected_route_information += report.route_information as u64;
+        self.injected_dnssl += report.dnssl as u64;
+        self.injected_captive_portal += report.captive_portal as u64;
+        self.injected_dhcpv6_dns_servers += report.dhcpv6_dns_servers as u64;
+        self.injected_dhcpv6_domain_list += report.dhcpv6_domain_list as u64;
+        self.injected_dhcpv6_captive_portal += report.dhcpv6_captive_portal as u64;
+    }
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    pub counters: Counters,
+    /// Currently advertised resolver addresses, in order of preference
+    pub resolvers: Vec<Ipv6Addr>,
+    pub resolvers_changed_at: Option<Instant>,
+    /// RA guard trusted routers, configured or learned
+    pub trusted_routers: Vec<RouterId>,
+    /// RA guard trusted DHCPv6 servers, configured (the same as the routers) or
+    /// learned
+    pub trusted_dhcpv6_servers: Vec<RouterId>,
+    pub rogue_routers: Vec<RogueSource>,
+    /// Packets from rogue sources beyond those tracked individually
+    pub rogue_overflow_packets: u64,
+    /// Rewritten cached RAs sent after the resolvers changed
+    pub unsolicited_ras: u64,
+    /// Valid router solicitations received with `--rs-ports`
+    pub router_solicitations: u64,
+    /// Rewritten cached RAs sent in response to router solicitations
+    pub solicited_ras: u64,
+    /// Replies sent to Information-Requests with `--dhcpv6-ports`
+    pub dhcpv6_information_replies: u64,
+}
+
+fn write_header(out: &mut String, name: &str, type_: &str, help: &str) {
+    writeln!(out, "# HELP {}_{} {}", PREFIX, name, help).unwrap();
+    writeln!(out, "# TYPE {}_{} {}", PREFIX, name, type_).unwrap();
+}
+
+impl Metrics {
+    /// Render in the Prometheus text exposition format.
+    ///
+    /// * https://prometheus.io/docs/instrumenting/exposition_formats/#text-based-format
+    pub fn render(&self, now: Instant) -> String {
+        let c = &self.counters;
+        let mut out = String::new();
+        write_header(&mut out, "packets_received_total", "counter", "Packets read from the netfilter queue.");
+        writeln!(out, "{}_packets_received_total {}", PREFIX, c.received).unwrap();
+        write_header(&mut out, "packets_rewritten_total", "counter", "Packets forwarded with modifications.");
+        writeln!(out, "{}_packets_rewritten_total {}", PREFIX, c.rewritten).unwrap();
+        write_header(
+            &mut out,
+            "packets_unchanged_total",
+            "counter",
+            "Packets forwarded without modifications since nothing needed changing.",
+        );
+        writeln!(out, "{}_packets_unchanged_total {}", PREFIX, c.unchanged).unwrap();
+        write_header(
+            &mut out,
+            "packets_skipped_total",
+            "counter",
+            "Packets that couldn't be modified, by reason and the policy verdict applied.",
+        );
+        for (reason, counts) in [
+            ("no_ip", c.no_ip),
+            ("parse_error", c.parse_error),
+            ("invalid", c.invalid),
+            ("rogue_router", c.rogue_router),
+            ("not_ra", c.not_ra),
+            ("not_dhcpv6", c.not_dhcpv6),
+            ("config_error", c.config_error),
+        ] {
+            for (verdict, count) in [("drop", counts.dropped), ("accept", counts.accepted)] {
+                writeln!(
+                    out,
+                    "{}_packets_skipped_total{{reason=\"{}\",verdict=\"{}\"}} {}",
+                    PREFIX,
+                    reason,
+                    verdict,
+                    count
+                ).unwrap();
+            }
+        }
+        write_header(&mut out, "options_injected_total", "counter", "Options generated in forwarded packets.");
+        for (option, count) in [
+            ("mtu", c.injected_mtu),
+            ("rdnss", c.injected_rdnss),
+            ("pref64", c.injected_pref64),
+            ("route_information", c.injected_route_information),
+            ("dnssl", c.injected_dnssl),
+            ("captive_portal", c.injected_captive_portal),
+            ("dhcpv6_dns_servers", c.injected_dhcpv6_dns_servers),
+            ("dhcpv6_domain_list", c.injected_dhcpv6_domain_list),
+            ("dhcpv6_captive_portal", c.injected_dhcpv6_captive_portal),
+        ] {
+            writeln!(out, "{}_options_injected_total{{option=\"{}\"}} {}", PREFIX, option, count).unwrap();
+        }
+        write_header(
+            &mut out,
+            "resolver",
+            "gauge",
+            "Currently advertised resolver addresses, priority 0 is most preferred.",
+        );
+        for (priority, address) in self.resolvers.iter().enumerate() {
+            writeln!(out, "{}_resolver{{address=\"{}\",priority=\"{}\"}} 1", PREFIX, address, priority).unwrap();
+        }
+        if let Some(changed_at) = self.resolvers_changed_at {
+            write_header(
+                &mut out,
+                "resolver_change_age_seconds",
+                "gauge",
+                "Seconds since the advertised resolver addresses last changed.",
+            );
+            writeln!(
+                out,
+                "{}_resolver_change_age_seconds {}",
+                PREFIX,
+                now.saturating_duration_since(changed_at).as_secs()
+            ).unwrap();
+        }
+        write_header(&mut out, "trusted_router", "gauge", "RA guard trusted upstream routers, configured or learned.");
+        for router in &self.trusted_routers {
+            writeln!(out, "{}_trusted_router{{router=\"{}\"}} 1", PREFIX, router).unwrap();
+        }
+        write_header(
+            &mut out,
+            "trusted_dhcpv6_server",
+            "gauge",
+            "RA guard trusted upstream DHCPv6 servers, configured or learned.",
+        );
+        for server in &self.trusted_dhcpv6_servers {
+            writeln!(out, "{}_trusted_dhcpv6_server{{server=\"{}\"}} 1", PREFIX, server).unwrap();
+        }
+        write_header(
+            &mut out,
+            "rogue_router_packets_total",
+            "counter",
+            "RAs and DHCPv6 replies from untrusted sources, by source address and MAC.",
+        );
+        for rogue in &self.rogue_routers {
+            writeln!(
+                out,
+                "{}_rogue_router_packets_total{{source=\"{}\",mac=\"{}\"}} {}",
+                PREFIX,
+                rogue.source,
+                rogue.mac.as_ref().map(format_mac).unwrap_or_default(),
+                rogue.packets
+            ).unwrap();
+        }
+        write_header(
+            &mut out,
+            "rogue_router_overflow_packets_total",
+            "counter",
+            "Packets from untrusted sources beyond the number tracked individually.",
+        );
+        writeln!(out, "{}_rogue_router_overflow_packets_total {}", PREFIX, self.rogue_overflow_packets).unwrap();
+        write_header(
+            &mut out,
+            "unsolicited_ras_sent_total",
+            "counter",
+            "Rewritten upstream RAs sent after the resolver addresses changed.",
+        );
+        writeln!(out, "{}_unsolicited_ras_sent_total {}", PREFIX, self.unsolicited_ras).unwrap();
+        write_header(&mut out, "router_solicitations_total", "counter", "Valid router solicitations received.");
+        writeln!(out, "{}_router_solicitations_total {}", PREFIX, self.router_solicitations).unwrap();
+        write_header(
+            &mut out,
+            "solicited_ras_sent_total",
+            "counter",
+            "Rewritten upstream RAs sent in response to router solicitations.",
+        );
+        writeln!(out, "{}_solicited_ras_sent_total {}", PREFIX, self.solicited_ras).unwrap();
+        write_header(
+            &mut out,
+            "dhcpv6_information_replies_sent_total",
+            "counter",
+            "Replies sent to DHCPv6 Information-Requests while no upstream DHCPv6 server was seen.",
+        );
+        writeln!(out, "{}_dhcpv6_information_replies_sent_total {}", PREFIX, self.dhcpv6_information_replies).unwrap();
+        return out;
+    }
+}
+
+fn handle(mut stream: TcpStream, metrics: &Mutex<Metrics>) -> Result<(), loga::Error> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT)).context("Error setting request read timeout")?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT)).context("Error setting request write timeout")?;
+
+    // Only the request line matters, read until the end of the headers
+    let mut request = vec![];
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        if request.len() > REQUEST_MAX_SIZE {
+            return Err(loga::err("Request headers too large"));
+        }
+        let count = stream.read(&mut buffer).context("Error reading request")?;
+        if count == 0 {
+            return Err(loga::err("Connection closed before end of request headers"));
+        }
+        request.extend_from_slice(&buffer[.. count]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let request_line = request.lines().next().unwrap_or_default();
+    let mut request_parts = request_line.split(' ');
+    let method = request_parts.next().unwrap_or_default();
+    let path = request_parts.next().unwrap_or_default();
+    let (status, body) = if method == "GET" && (path == "/metrics" || path == "/") {
+        ("200 OK", metrics.lock().unwrap().render(Instant::now()))
+    } else {
+        ("404 Not Found", "Not found\n".to_string())
+    };
+    let mut response =
+        format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            status,
+            CONTENT_TYPE,
+            body.len()
+        );
+    response.push_str(&body);
+    stream.write_all(response.as_bytes()).context_with("Error writing response", ea!(path = path))?;
+    return Ok(());
+}
+
+/// Serve metrics over HTTP. Requests are handled one at a time. Only returns on
+/// error.
+pub fn serve(log: &Log, bind: SocketAddr, metrics: Arc<Mutex<Metrics>>) -> Result<(), loga::Error> {
+    let listener = TcpListener::bind(bind).context_with("Error binding metrics listener", ea!(bind = bind))?;
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                log.log_err(loga::WARN, e.context("Error accepting metrics connection"));
+                continue;
+            },
+        };
+        if let Err(e) = handle(stream, &metrics) {
+            log.log_err(loga::DEBUG, e.context("Error handling metrics request"));
+        }
+    }
+    return Ok(());
+}
@@ -0,0 +1,76 @@
+use {
+    crate::{
+        guardlib::{
+            RogueSource,
+            RouterId,
+        },
+        metricslib::{
+            Counters,
+            Metrics,
+            PolicyCounts,
+        },
+    },
+    std::time::{
+        Duration,
+        Instant,
+    },
+};
+
+#[test]
+fn test_render() {
+    let now = Instant::now();
+    let metrics = Metrics {
+        counters: Counters {
+            received: 10,
+            rewritten: 4,
+            no_ip: PolicyCounts {
+                dropped: 3,
+                accepted: 0,
+            },
+            invalid: PolicyCounts {
+                dropped: 1,
+                accepted: 0,
+            },
+            injected_rdnss: 2,
+            ..Default::default()
+        },
+        resolvers: vec!["2400::1".parse().unwrap(), "fd00::1".parse().unwrap()],
+        resolvers_changed_at: Some(now - Duration::from_secs(30)),
+        trusted_routers: vec![RouterId::parse("fe80::1@02:00:00:00:00:01").unwrap()],
+        trusted_dhcpv6_servers: vec![RouterId::parse("fe80::2").unwrap()],
+        rogue_routers: vec![RogueSource {
+            source: "fe80::bad".parse().unwrap(),
+            mac: None,
+            packets: 5,
+            last_seen: now,
+        }],
+        rogue_overflow_packets: 0,
+        unsolicited_ras: 1,
+        router_solicitations: 3,
+        solicited_ras: 2,
+        dhcpv6_information_replies: 4,
+    };
+    let got = metrics.render(now);
+    for want in [
+        "# TYPE mangle_ip_configure_packets_received_total counter\n",
+        "mangle_ip_configure_packets_received_total 10\n",
+        "mangle_ip_configure_packets_rewritten_total 4\n",
+        "mangle_ip_configure_packets_skipped_total{reason=\"no_ip\",verdict=\"drop\"} 3\n",
+        "mangle_ip_configure_packets_skipped_total{reason=\"no_ip\",verdict=\"accept\"} 0\n",
+        "mangle_ip_configure_packets_skipped_total{reason=\"invalid\",verdict=\"drop\"} 1\n",
+        "mangle_ip_configure_options_injected_total{option=\"rdnss\"} 2\n",
+        "mangle_ip_configure_resolver{address=\"2400::1\",priority=\"0\"} 1\n",
+        "mangle_ip_configure_resolver{address=\"fd00::1\",priority=\"1\"} 1\n",
+        "mangle_ip_configure_resolver_change_age_seconds 30\n",
+        "mangle_ip_configure_trusted_router{router=\"fe80::1@02:00:00:00:00:01\"} 1\n",
+        "mangle_ip_configure_trusted_dhcpv6_server{server=\"fe80::2\"} 1\n",
+        "mangle_ip_configure_rogue_router_packets_total{source=\"fe80::bad\",mac=\"\"} 5\n",
+        "mangle_ip_configure_rogue_router_overflow_packets_total 0\n",
+        "mangle_ip_configure_unsolicited_ras_sent_total 1\n",
+        "mangle_ip_configure_router_solicitations_total 3\n",
+        "mangle_ip_configure_solicited_ras_sent_total 2\n",
+        "mangle_ip_configure_dhcpv6_information_replies_sent_total 4\n",
+    ] {
+        assert!(got.contains(want), "Missing {:?} in:\n{}", want, got);
+    }
+}
@@ -0,0 +1,284 @@
+#[cfg(test)]
+mod test_fixtures;
+
+// Pcap
+//
+// * https://datatracker.ietf.org/doc/html/draft-ietf-opsawg-pcap
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b23c4d;
+const PCAP_HEADER_SIZE: usize = 24;
+const PCAP_RECORD_HEADER_SIZE: usize = 16;
+// Pcapng
+//
+// * https://datatracker.ietf.org/doc/html/draft-ietf-opsawg-pcapng
+const PCAPNG_BLOCK_SECTION_HEADER: u32 = 0x0a0d0d0a;
+const PCAPNG_BLOCK_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_BLOCK_SIMPLE_PACKET: u32 = 3;
+const PCAPNG_BLOCK_ENHANCED_PACKET: u32 = 6;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+const PCAPNG_OPT_END: u16 = 0;
+const PCAPNG_OPT_IF_TSRESOL: u16 = 9;
+// Link types
+//
+// * https://www.tcpdump.org/linktypes.html
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+
+pub struct PcapPacket {
+    /// Nanoseconds since the epoch
+    pub timestamp: u64,
+    /// The IPv6 packet, without any link layer header
+    pub data: Vec<u8>,
+}
+
+fn read_u16(source: &[u8], start: usize, big_endian: bool) -> Option<u16> {
+    let bytes = source.get(start .. start + 2)?.try_into().unwrap();
+    return Some(if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    });
+}
+
+fn read_u32(source: &[u8], start: usize, big_endian: bool) -> Option<u32> {
+    let bytes = source.get(start .. start + 4)?.try_into().unwrap();
+    return Some(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    });
+}
+
+/// Returns the IPv6 packet in a captured frame, or None if the frame isn't IPv6 or
+/// the link type isn't supported.
+fn strip_link(link_type: u32, frame: &[u8]) -> Option<&[u8]> {
+    match link_type {
+        LINKTYPE_RAW | LINKTYPE_IPV6 => {
+            if frame.first()? >> 4 != 6 {
+                return None;
+            }
+            return Some(frame);
+        },
+        LINKTYPE_ETHERNET => {
+            let mut at_ethertype = 12;
+            loop {
+                match read_u16(frame, at_ethertype, true)? {
+                    ETHERTYPE_VLAN | ETHERTYPE_QINQ => at_ethertype += 4,
+                    ETHERTYPE_IPV6 => return frame.get(at_ethertype + 2..),
+                    _ => return None,
+                }
+            }
+        },
+        LINKTYPE_LINUX_SLL => {
+            if read_u16(frame, 14, true)? != ETHERTYPE_IPV6 {
+                return None;
+            }
+            return frame.get(16..);
+        },
+        LINKTYPE_LINUX_SLL2 => {
+            if read_u16(frame, 0, true)? != ETHERTYPE_IPV6 {
+                return None;
+            }
+            return frame.get(20..);
+        },
+        _ => return None,
+    }
+}
+
+fn read_pcap(source: &[u8]) -> Result<Vec<PcapPacket>, loga::Error> {
+    let magic_le = read_u32(source, 0, false).unwrap();
+    let (big_endian, nanos) = match (magic_le, read_u32(source, 0, true).unwrap()) {
+        (PCAP_MAGIC_MICROS, _) => (false, false),
+        (PCAP_MAGIC_NANOS, _) => (false, true),
+        (_, PCAP_MAGIC_MICROS) => (true, false),
+        (_, PCAP_MAGIC_NANOS) => (true, true),
+        _ => unreachable!(),
+    };
+    let truncated = || loga::err("Pcap file is truncated");
+    let link_type = read_u32(source, 20, big_endian).ok_or_else(truncated)? & 0x0fffffff;
+    let mut out = vec![];
+    let mut at_record_start = PCAP_HEADER_SIZE;
+    while at_record_start < source.len() {
+        let seconds = read_u32(source, at_record_start, big_endian).ok_or_else(truncated)? as u64;
+        let fraction = read_u32(source, at_record_start + 4, big_endian).ok_or_else(truncated)? as u64;
+        let captured_length = read_u32(source, at_record_start + 8, big_endian).ok_or_else(truncated)? as usize;
+        let data_start = at_record_start + PCAP_RECORD_HEADER_SIZE;
+        let frame = source.get(data_start .. data_start + captured_length).ok_or_else(truncated)?;
+        if let Some(data) = strip_link(link_type, frame) {
+            out.push(PcapPacket {
+                timestamp: seconds * 1_000_000_000 + if nanos {
+                    fraction
+                } else {
+                    fraction * 1000
+                },
+                data: data.to_vec(),
+            });
+        }
+        at_record_start = data_start + captured_length;
+    }
+    return Ok(out);
+}
+
+struct PcapngInterface {
+    link_type: u32,
+    /// Nanoseconds per timestamp unit, or units per nanosecond if negative
+    resolution: i64,
+}
+
+fn pcapng_resolution(tsresol: u8) -> i64 {
+    // High bit set means a negative power of 2, otherwise a negative power of 10
+    let units_per_second = if tsresol & 0x80 != 0 {
+        1u64.checked_shl((tsresol & 0x7f) as u32).unwrap_or(u64::MAX)
+    } else {
+        10u64.checked_pow(tsresol as u32).unwrap_or(u64::MAX)
+    };
+    if units_per_second <= 1_000_000_000 {
+        return (1_000_000_000 / units_per_second) as i64;
+    } else {
+        return -((units_per_second / 1_000_000_000) as i64);
+    }
+}
+
+fn read_pcapng(source: &[u8]) -> Result<Vec<PcapPacket>, loga::Error> {
+    let truncated = || loga::err("Pcapng file is truncated");
+    let mut big_endian = false;
+    let mut interfaces = vec![];
+    let mut out = vec![];
+    let mut at_block_start = 0;
+    while at_block_start < source.len() {
+        let block_type = read_u32(source, at_block_start, big_endian).ok_or_else(truncated)?;
+        if block_type == PCAPNG_BLOCK_SECTION_HEADER {
+            // Each section may have a different byte order, and resets interfaces
+            big_endian = match read_u32(source, at_block_start + 8, false).ok_or_else(truncated)? {
+                PCAPNG_BYTE_ORDER_MAGIC => false,
+                m if m.swap_bytes() == PCAPNG_BYTE_ORDER_MAGIC => true,
+                _ => return Err(loga::err("Invalid pcapng byte order magic")),
+            };
+            interfaces.clear();
+        }
+        let block_length = read_u32(source, at_block_start + 4, big_endian).ok_or_else(truncated)? as usize;
+        if block_length < 12 || block_length % 4 != 0 {
+            return Err(loga::err("Invalid pcapng block length"));
+        }
+        let body = source.get(at_block_start + 8 .. at_block_start + block_length - 4).ok_or_else(truncated)?;
+        match block_type {
+            PCAPNG_BLOCK_INTERFACE_DESCRIPTION => {
+                let mut interface = PcapngInterface {
+                    link_type: read_u16(body, 0, big_endian).ok_or_else(truncated)? as u32,
+                    resolution: 1000,
+                };
+                let mut at_option_start = 8;
+                while at_option_start + 4 <= body.len() {
+                    let code = read_u16(body, at_option_start, big_endian).unwrap();
+                    let length = read_u16(body, at_option_start + 2, big_endian).unwrap() as usize;
+                    if code == PCAPNG_OPT_END {
+                        break;
+                    }
+                    if code == PCAPNG_OPT_IF_TSRESOL {
+                        interface.resolution = pcapng_resolution(*body.get(at_option_start + 4).ok_or_else(truncated)?);
+                    }
+                    at_option_start += 4 + length.div_ceil(4) * 4;
+                }
+                interfaces.push(interface);
+            },
+            PCAPNG_BLOCK_ENHANCED_PACKET => {
+                let interface_id = read_u32(body, 0, big_endian).ok_or_else(truncated)? as usize;
+                let interface =
+                    interfaces.get(interface_id).ok_or_else(|| loga::err("Packet references unknown interface"))?;
+                let timestamp =
+                    (read_u32(body, 4, big_endian).ok_or_else(truncated)? as u64) << 32 |
+                        read_u32(body, 8, big_endian).ok_or_else(truncated)? as u64;
+                let captured_length = read_u32(body, 12, big_endian).ok_or_else(truncated)? as usize;
+                let frame = body.get(20 .. 20 + captured_length).ok_or_else(truncated)?;
+                if let Some(data) = strip_link(interface.link_type, frame) {
+                    out.push(PcapPacket {
+                        timestamp: if interface.resolution >= 0 {
+                            timestamp.saturating_mul(interface.resolution as u64)
+                        } else {
+                            timestamp / (-interface.resolution) as u64
+                        },
+                        data: data.to_vec(),
+                    });
+                }
+            },
+            PCAPNG_BLOCK_SIMPLE_PACKET => {
+                // Always interface 0, no timestamp. Captured length is limited by the
+                // block size.
+                let interface = interfaces.first().ok_or_else(|| loga::err("Packet references unknown interface"))?;
+                let original_length = read_u32(body, 0, big_endian).ok_or_else(truncated)? as usize;
+                let frame = body.get(4..).ok_or_else(truncated)?;
+                if let Some(data) = strip_link(interface.link_type, &frame[.. original_length.min(frame.len())]) {
+                    out.push(PcapPacket {
+                        timestamp: 0,
+                        data: data.to_vec(),
+                    });
+                }
+            },
+            _ => { },
+        }
+        at_block_start += block_length;
+    }
+    return Ok(out);
+}
+
+/// Read the IPv6 packets in a pcap or pcapng file. Frames that aren't IPv6 are
+/// skipped.
+pub fn read_packets(source: &[u8]) -> Result<Vec<PcapPacket>, loga::Error> {
+    let Some(magic) = read_u32(source, 0, false) else {
+        return Err(loga::err("File is too short to be a pcap or pcapng file"));
+    };
+    if magic == PCAPNG_BLOCK_SECTION_HEADER {
+        return read_pcapng(source);
+    }
+    if [magic, magic.swap_bytes()].iter().any(|m| *m == PCAP_MAGIC_MICROS || *m == PCAP_MAGIC_NANOS) {
+        return read_pcap(source);
+    }
+    return Err(loga::err("Unrecognized file format, not pcap or pcapng"));
+}
+
+/// Write IPv6 packets as a little endian, nanosecond resolution pcap file with the
+/// raw IP link type.
+pub fn write_packets(packets: &[PcapPacket]) -> Vec<u8> {
+    let mut out = vec![];
+    out.extend_from_slice(&PCAP_MAGIC_NANOS.to_le_bytes());
+
+    // Version 2.4
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&4u16.to_le_bytes());
+
+    // Reserved
+    out.extend_from_slice(&[0; 8]);
+
+    // Snap length
+    out.extend_from_slice(&65535u32.to_le_bytes());
+    out.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+    for packet in packets {
+        out.extend_from_slice(&((packet.timestamp / 1_000_000_000) as u32).to_le_bytes());
+        out.extend_from_slice(&((packet.timestamp % 1_000_000_000) as u32).to_le_bytes());
+        out.extend_from_slice(&(packet.data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(packet.data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&packet.data);
+    }
+    return out;
+}
+
+/// Run each IPv6 packet in a pcap or pcapng file through `mangle`, writing returned
+/// packets to a pcap file. Packets `mangle` returns None for are omitted.
+pub fn replay(source: &[u8], mut mangle: impl FnMut(&[u8]) -> Option<Vec<u8>>) -> Result<Vec<u8>, loga::Error> {
+    let mut out = vec![];
+    for packet in read_packets(source)? {
+        if let Some(data) = mangle(&packet.data) {
+            out.push(PcapPacket {
+                timestamp: packet.timestamp,
+                data: data,
+            });
+        }
+    }
+    return Ok(write_packets(&out));
+}
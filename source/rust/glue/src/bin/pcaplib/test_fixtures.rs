@@ -0,0 +1,89 @@
+use {
+    crate::{
+        build_shared,
+        replay_capture,
+        Args,
+    },
+    aargvark::{
+        vark_explicit,
+        VarkRet,
+    },
+    loga::Log,
+    std::{
+        env,
+        fs,
+        path::{
+            Path,
+            PathBuf,
+        },
+    },
+};
+
+fn fixtures_dir() -> PathBuf {
+    return Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures").join("pcap");
+}
+
+fn run_fixture(dir: &Path) -> Result<(), String> {
+    let input =
+        ["in.pcap", "in.pcapng"]
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.exists())
+            .ok_or_else(|| "Missing in.pcap or in.pcapng".to_string())?;
+
+    // `args.json` in each fixture directory has the flags to run with, as a list of
+    // strings. Parsed and processed the same as `--pcap-in`, so flag defaults apply.
+    let mut args =
+        serde_json::from_slice::<Vec<String>>(
+            &fs::read(dir.join("args.json")).map_err(|e| format!("Error reading args.json: {}", e))?,
+        ).map_err(|e| format!("Invalid args.json: {}", e))?;
+    args.push("--pcap-in".to_string());
+    args.push(input.to_string_lossy().to_string());
+    let args = match vark_explicit::<Args>(None, args) {
+        Ok(VarkRet::Ok(args)) => args,
+        Ok(VarkRet::Help(_)) => return Err("Invalid args.json: requested help".to_string()),
+        Err(e) => return Err(format!("Invalid args.json: {:?}", e)),
+    };
+    let shared = build_shared(&args, &Log::new_root(loga::INFO)).map_err(|e| format!("Invalid args.json: {}", e))?;
+    let got =
+        replay_capture(&shared, args.pcap_in.as_ref().unwrap()).map_err(|e| format!("Error replaying: {}", e))?;
+    let golden_path = dir.join("out.pcap");
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&golden_path, &got).map_err(|e| format!("Error writing golden: {}", e))?;
+        return Ok(());
+    }
+    let want = fs::read(&golden_path).map_err(|e| format!("Error reading golden out.pcap: {}", e))?;
+    if got != want {
+        let first_difference =
+            got.iter().zip(want.iter()).position(|(a, b)| a != b).unwrap_or(got.len().min(want.len()));
+        return Err(
+            format!(
+                "Output differs from golden at byte {} (got {} bytes, want {}), set UPDATE_GOLDEN=1 to accept",
+                first_difference,
+                got.len(),
+                want.len()
+            ),
+        );
+    }
+    return Ok(());
+}
+
+/// Replay every `fixtures/pcap/<name>/in.pcap[ng]` and compare against `out.pcap`.
+#[test]
+fn test_pcap_fixtures() {
+    let mut dirs =
+        fs::read_dir(fixtures_dir())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.is_dir())
+            .collect::<Vec<_>>();
+    dirs.sort();
+    assert!(!dirs.is_empty());
+    let mut failures = vec![];
+    for dir in dirs {
+        if let Err(e) = run_fixture(&dir) {
+            failures.push(format!("{}: {}", dir.file_name().unwrap().to_string_lossy(), e));
+        }
+    }
+    assert!(failures.is_empty(), "Fixture failures:\n{}", failures.join("\n"));
+}